@@ -0,0 +1,135 @@
+/*
+ * Only the replacement half of smashline is exposed to C. Callback registrations, agent storage, the
+ * script catalogue, validation and hash labels take or return Rust types and are only available to Rust plugins.
+ */
+
+#ifndef SMASHLINE_HOOK_H
+#define SMASHLINE_HOOK_H
+
+/* Generated with cbindgen from src/capi.rs and src/abi.rs, do not edit by hand. */
+
+#include <stdbool.h>
+#include <stdint.h>
+
+#define SMASHLINE_RESULT_OK 0
+
+#define SMASHLINE_RESULT_NULL_ARGUMENT -1
+
+#define SMASHLINE_RESULT_INVALID_STRING -2
+
+#define SMASHLINE_RESULT_INVALID_CATEGORY -3
+
+#define SMASHLINE_RESULT_REFUSED -4
+
+#define SMASHLINE_RESULT_CONFLICT -5
+
+#define ABI_VERSION_MAJOR 2
+
+#define ABI_VERSION_MINOR 1
+
+#define SMASHLINE_ACMD_GAME 0
+
+#define SMASHLINE_ACMD_EFFECT 1
+
+#define SMASHLINE_ACMD_SOUND 2
+
+#define SMASHLINE_ACMD_EXPRESSION 3
+
+typedef struct AbiFeatures {
+  uint64_t bits;
+} AbiFeatures;
+#define AbiFeatures_CRASH_REPORTS (AbiFeatures){ .bits = (uint64_t)(1 << 0) }
+#define AbiFeatures_PANIC_SHIMS (AbiFeatures){ .bits = (uint64_t)(1 << 1) }
+#define AbiFeatures_UNWIND_INFO (AbiFeatures){ .bits = (uint64_t)(1 << 2) }
+#define AbiFeatures_EXPLICIT_REGISTRATION (AbiFeatures){ .bits = (uint64_t)(1 << 3) }
+#define AbiFeatures_C_API (AbiFeatures){ .bits = (uint64_t)(1 << 4) }
+#define AbiFeatures_KIND_FRAME_CALLBACKS (AbiFeatures){ .bits = (uint64_t)(1 << 5) }
+#define AbiFeatures_ORDERED_CALLBACKS (AbiFeatures){ .bits = (uint64_t)(1 << 6) }
+#define AbiFeatures_FRAME_PRIORITIES (AbiFeatures){ .bits = (uint64_t)(1 << 7) }
+#define AbiFeatures_STATUS_CHANGE (AbiFeatures){ .bits = (uint64_t)(1 << 8) }
+#define AbiFeatures_AGENT_EVENTS (AbiFeatures){ .bits = (uint64_t)(1 << 9) }
+#define AbiFeatures_AGENT_STORAGE (AbiFeatures){ .bits = (uint64_t)(1 << 10) }
+#define AbiFeatures_SCRIPT_CATALOGUE (AbiFeatures){ .bits = (uint64_t)(1 << 11) }
+#define AbiFeatures_VALIDATION (AbiFeatures){ .bits = (uint64_t)(1 << 12) }
+#define AbiFeatures_HASH_LABELS (AbiFeatures){ .bits = (uint64_t)(1 << 13) }
+#define AbiFeatures_BY_NAME_REGISTRATION (AbiFeatures){ .bits = (uint64_t)(1 << 14) }
+#define AbiFeatures_ITEM_SCRIPTS (AbiFeatures){ .bits = (uint64_t)(1 << 15) }
+#define AbiFeatures_ITEM_CALLBACKS (AbiFeatures){ .bits = (uint64_t)(1 << 16) }
+#define AbiFeatures_ACMD_AGENT_TARGET (AbiFeatures){ .bits = (uint64_t)(1 << 17) }
+#define AbiFeatures_ITEM_CALLBACK_ORDER (AbiFeatures){ .bits = (uint64_t)(1 << 18) }
+
+typedef struct AbiInfo {
+  uint32_t major;
+  uint32_t minor;
+  uint64_t features;
+} AbiInfo;
+
+typedef struct SmashlineAcmdScript {
+  uint64_t agent;
+  uint64_t script;
+  uint32_t category;
+  bool low_priority;
+  const void *replacement;
+  const void **original;
+} SmashlineAcmdScript;
+
+typedef struct SmashlineStatusScript {
+  uint64_t agent;
+  int32_t status;
+  int32_t condition;
+  bool low_priority;
+  const void *replacement;
+  const void **original;
+} SmashlineStatusScript;
+
+typedef struct SmashlineCommonStatusScript {
+  int32_t status;
+  int32_t condition;
+  const void *replacement;
+  const void **original;
+} SmashlineCommonStatusScript;
+
+typedef struct SmashlineMoveCustomizer {
+  uint64_t agent;
+  bool low_priority;
+  const void *replacement;
+  const void **original;
+} SmashlineMoveCustomizer;
+
+typedef struct SmashlineSymbolReplacement {
+  const char *module;
+  const char *symbol;
+  const void *replacement;
+  const void **original;
+} SmashlineSymbolReplacement;
+
+typedef struct SmashlineFrameReplacement {
+  int32_t kind;
+  bool is_fighter;
+  const void *replacement;
+  const void **original;
+} SmashlineFrameReplacement;
+
+AbiInfo smashline_get_abi_info(void);
+
+bool smashline_check_abi(const void *plugin, uint32_t major, uint32_t minor, uint64_t required_features);
+
+int32_t smashline_register_plugin(const void *address);
+
+int32_t smashline_replace_acmd_script(const SmashlineAcmdScript *script);
+
+int32_t smashline_replace_status_script(const SmashlineStatusScript *script);
+
+int32_t smashline_replace_common_status_script(const SmashlineCommonStatusScript *script);
+
+int32_t smashline_replace_move_customizer(const SmashlineMoveCustomizer *customizer);
+
+int32_t smashline_replace_symbol(const SmashlineSymbolReplacement *replacement);
+
+int32_t smashline_replace_fighter_frame(const SmashlineFrameReplacement *replacement);
+
+int32_t smashline_replace_weapon_frame(const SmashlineFrameReplacement *replacement);
+
+int32_t smashline_replace_agent_frame_main(const SmashlineFrameReplacement *replacement);
+
+#endif /* SMASHLINE_HOOK_H */
@@ -0,0 +1,142 @@
+use std::ffi::c_void;
+
+use parking_lot::Mutex;
+
+use crate::nx::svc;
+
+// Bump the major version whenever an existing export changes signature, and the minor version when
+// exports are added. Plugins built against a newer minor version than this will be refused.
+// 2.0: the ACMD/status replacement exports take extern "C-unwind" functions so a panic can reach the shims
+pub const ABI_VERSION_MAJOR: u32 = 2;
+pub const ABI_VERSION_MINOR: u32 = 1;
+
+bitflags! {
+    #[repr(C)]
+    pub struct AbiFeatures : u64 {
+        const CRASH_REPORTS         = 1 << 0;
+        const PANIC_SHIMS           = 1 << 1;
+        const UNWIND_INFO           = 1 << 2;
+        const EXPLICIT_REGISTRATION = 1 << 3;
+        const C_API                 = 1 << 4;
+        const KIND_FRAME_CALLBACKS  = 1 << 5;
+        const ORDERED_CALLBACKS     = 1 << 6;
+        const FRAME_PRIORITIES      = 1 << 7;
+        const STATUS_CHANGE         = 1 << 8;
+        const AGENT_EVENTS          = 1 << 9;
+        const AGENT_STORAGE         = 1 << 10;
+        const SCRIPT_CATALOGUE      = 1 << 11;
+        const VALIDATION            = 1 << 12;
+        const HASH_LABELS           = 1 << 13;
+        const BY_NAME_REGISTRATION  = 1 << 14;
+        const ITEM_SCRIPTS          = 1 << 15;
+        const ITEM_CALLBACKS        = 1 << 16;
+        const ACMD_AGENT_TARGET     = 1 << 17;
+        const ITEM_CALLBACK_ORDER   = 1 << 18;
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct AbiInfo {
+    pub major: u32,
+    pub minor: u32,
+    pub features: u64
+}
+
+// Why a registration didn't go through, so the C API can report it
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RegistrationError {
+    // the plugin failed the ABI check
+    Refused,
+    // something with a higher priority already replaced it
+    Conflict
+}
+
+lazy_static! {
+    static ref REFUSED_PLUGINS: Mutex<Vec<(usize, usize)>> = Mutex::new(Vec::new());
+}
+
+pub fn get_abi_info() -> AbiInfo {
+    AbiInfo {
+        major: ABI_VERSION_MAJOR,
+        minor: ABI_VERSION_MINOR,
+        features: AbiFeatures::all().bits()
+    }
+}
+
+pub fn is_compatible(required: &AbiInfo) -> bool {
+    let supported = get_abi_info();
+    required.major == supported.major
+    && required.minor <= supported.minor
+    && (required.features & supported.features) == required.features
+}
+
+pub fn is_refused(address: usize) -> bool {
+    let refused = REFUSED_PLUGINS.lock();
+    refused.iter().any(|(begin, end)| *begin <= address && address < *end)
+}
+
+// Every registration entry point goes through here with an address inside of the calling plugin
+pub fn accept_registration(address: usize) -> bool {
+    if is_refused(address) {
+        println!("[smashline::abi] Refusing registration from plugin with an incompatible ABI | Address: {:#x}", address);
+        return false;
+    }
+    crate::unwind::register_skyline_plugin(address);
+    true
+}
+
+pub fn refuse_plugin(address: usize) {
+    if let Ok(mem_info) = svc::query_memory(address) {
+        let range = (mem_info.mem_info.base_address, mem_info.mem_info.base_address + mem_info.mem_info.size);
+        let mut refused = REFUSED_PLUGINS.lock();
+        if !refused.contains(&range) {
+            refused.push(range);
+        }
+    }
+}
+
+pub fn remove_refused_plugins(range: (usize, usize)) {
+    REFUSED_PLUGINS.lock().retain(|(begin, _)| !(range.0 <= *begin && *begin < range.1));
+}
+
+pub fn log_mismatch(required: &AbiInfo) {
+    let supported = get_abi_info();
+    if required.major != supported.major {
+        println!("[smashline::abi] Plugin was built against smashline ABI {}.{}, but this smashline_hook provides {}.{}. Rebuild the plugin against a matching smashline.", required.major, required.minor, supported.major, supported.minor);
+    } else if required.minor > supported.minor {
+        println!("[smashline::abi] Plugin requires smashline ABI {}.{}, but this smashline_hook only provides {}.{}. Update smashline_hook.", required.major, required.minor, supported.major, supported.minor);
+    } else {
+        println!("[smashline::abi] Plugin requires smashline features {:#x} which are not provided ({:#x}). Update smashline_hook.", required.features, supported.features);
+    }
+}
+
+// Loading still goes ahead so plugins from before the handshake keep working, but any signature change since
+// they were built will crash, so make sure this can't be missed in the log
+pub fn log_unverified(plugin: &str) {
+    let supported = get_abi_info();
+    println!("[smashline::abi] ================================================================");
+    println!("[smashline::abi] WARNING: {} does not export 'smashline_required_abi'", plugin);
+    println!("[smashline::abi] Its ABI can't be checked against smashline {}.{}, it is loaded UNVERIFIED.", supported.major, supported.minor);
+    println!("[smashline::abi] Rebuild it against the current smashline if it crashes or misbehaves.");
+    println!("[smashline::abi] ================================================================");
+}
+
+#[no_mangle]
+pub extern "C" fn smashline_get_abi_info() -> AbiInfo {
+    get_abi_info()
+}
+
+// Plugins should call this once at startup, before registering anything. Registrations from a plugin that
+// fails this check are refused.
+#[no_mangle]
+pub unsafe extern "C" fn smashline_check_abi(plugin: *const c_void, major: u32, minor: u32, required_features: u64) -> bool {
+    let required = AbiInfo { major, minor, features: required_features };
+    if is_compatible(&required) {
+        true
+    } else {
+        log_mismatch(&required);
+        refuse_plugin(plugin as usize);
+        false
+    }
+}
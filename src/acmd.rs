@@ -4,6 +4,9 @@ use parking_lot::Mutex;
 use std::collections::HashMap;
 use std::hash::{Hash, Hasher};
 
+use crate::abi::RegistrationError;
+use crate::shims::{ShimKind, ShimOwner};
+
 #[derive(PartialEq, Clone, Copy)]
 pub enum Category {
     ACMD_GAME,
@@ -12,12 +15,63 @@ pub enum Category {
     ACMD_EXPRESSION
 }
 
+// Which of an agent's ACMD agents a replacement is installed on, the regular one, the `_share` one, or both
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum AgentTarget {
+    All,
+    Main,
+    Share
+}
+
+impl AgentTarget {
+    pub fn matches(self, is_share: bool) -> bool {
+        match self {
+            AgentTarget::All => true,
+            AgentTarget::Main => !is_share,
+            AgentTarget::Share => is_share
+        }
+    }
+
+    pub fn overlaps(self, other: AgentTarget) -> bool {
+        self == AgentTarget::All || other == AgentTarget::All || self == other
+    }
+
+    // What is left of this target once `other` takes over part of it, None if nothing is
+    pub fn without(self, other: AgentTarget) -> Option<AgentTarget> {
+        match (self, other) {
+            (AgentTarget::All, AgentTarget::Main) => Some(AgentTarget::Share),
+            (AgentTarget::All, AgentTarget::Share) => Some(AgentTarget::Main),
+            _ => None
+        }
+    }
+
+    // Given the target and priority (true if low) of every existing replacement of the same script, the ones a
+    // new replacement for this target takes over, with the target each keeps running on (None if it is replaced
+    // entirely). An All replacement can overlap both a Main and a Share one, and a Main or Share one only takes
+    // one side of an All. Err with the index of a high priority one that is in the way
+    pub fn find_replaced(self, existing: &[(AgentTarget, bool)]) -> Result<Vec<(usize, Option<AgentTarget>)>, usize> {
+        let mut replaced = Vec::new();
+        for (index, (target, low_priority)) in existing.iter().enumerate() {
+            if !target.overlaps(self) {
+                continue;
+            }
+            if !low_priority {
+                return Err(index);
+            }
+            replaced.push((index, target.without(self)));
+        }
+        Ok(replaced)
+    }
+}
+
 pub struct ScriptInfo {
     pub script: Hash40,
     pub original: Option<&'static mut *const extern "C" fn()>,
     pub low_priority: bool,
     pub bind_fn: *const extern "C" fn(),
-    pub backup: *const extern "C" fn() // serves same purpose as `original` except for guaranteeing something on uninstallation
+    pub backup: *const extern "C" fn(), // serves same purpose as `original` except for guaranteeing something on uninstallation
+    pub shim: Option<usize>,
+    pub target: AgentTarget
 }
 
 impl ScriptInfo {
@@ -27,7 +81,42 @@ impl ScriptInfo {
             original: self.original.take(),
             low_priority: self.low_priority,
             bind_fn: self.bind_fn,
-            backup: self.backup
+            backup: self.backup,
+            shim: self.shim.take(),
+            target: self.target
+        }
+    }
+
+    // agent/script, by name when the hashes have labels
+    pub fn describe(&self, agent: Hash40) -> String {
+        crate::labels::format_script(agent, self.script)
+    }
+
+    pub fn set_backup(&mut self, backup: *const extern "C" fn()) {
+        self.backup = backup;
+        if let Some(slot) = self.shim {
+            crate::shims::set_fallback(ShimKind::Acmd, slot, backup);
+        }
+    }
+
+    // the function that actually gets placed on the agent
+    pub fn get_installed_fn(&self) -> *const extern "C" fn() {
+        match self.shim {
+            Some(slot) => crate::shims::get_shim(ShimKind::Acmd, slot),
+            None => self.bind_fn
+        }
+    }
+
+    pub fn release_shim(&mut self) {
+        if let Some(slot) = self.shim.take() {
+            crate::shims::release(ShimKind::Acmd, slot);
+        }
+    }
+
+    // for a registration that was never installed on any agent
+    pub fn discard_shim(&mut self) {
+        if let Some(slot) = self.shim.take() {
+            crate::shims::discard(ShimKind::Acmd, slot);
         }
     }
 }
@@ -70,7 +159,7 @@ pub fn nro_load(info: &NroInfo) {
         Category::ACMD_EXPRESSION
     ];
     match info.name {
-        "common" | "item" | "" => {},
+        "common" | "" => {},
         _ => {
             for cat in CATEGORIES.iter() {
                 crate::scripts::patch_create_agent_animcmd(info, *cat);
@@ -102,6 +191,8 @@ pub unsafe fn remove_acmd_scripts(range: (usize, usize)) {
                 let as_usize = script.bind_fn as *const () as usize;
                 if !(begin <= as_usize && as_usize < end) {
                     new_vec.push(script.transfer());
+                } else {
+                    script.release_shim();
                 }
             }
             *script_list = new_vec;
@@ -109,36 +200,159 @@ pub unsafe fn remove_acmd_scripts(range: (usize, usize)) {
     }
 }
 
+pub fn try_replace_acmd_script(agent: Hash40, script: Hash40, original: Option<&'static mut *const extern "C" fn()>, category: Category, low_priority: bool, target: AgentTarget, bind_fn: *const extern "C-unwind" fn()) -> Result<(), RegistrationError> {
+    if !crate::abi::accept_registration(bind_fn as usize) {
+        return Err(RegistrationError::Refused);
+    }
+
+    // stored like the game's own functions, only the shim ever calls it and it does so as C-unwind
+    let bind_fn = bind_fn as *const extern "C" fn();
+    let shim = crate::shims::allocate(ShimKind::Acmd, bind_fn, ShimOwner::Acmd { agent, script });
+    let info = ScriptInfo { script, original, low_priority, bind_fn, backup: 0 as _, shim, target };
+    add_acmd_script(agent, info, category)
+}
+
 #[no_mangle]
-pub extern "Rust" fn replace_acmd_script(agent: Hash40, script: Hash40, original: Option<&'static mut *const extern "C" fn()>, category: Category, low_priority: bool, bind_fn: *const extern "C" fn()) {
-    crate::unwind::register_skyline_plugin(bind_fn as usize);
-    
-    let mut info = ScriptInfo { script, original, low_priority, bind_fn, backup: 0 as _ };
+pub extern "Rust" fn replace_acmd_script(agent: Hash40, script: Hash40, original: Option<&'static mut *const extern "C" fn()>, category: Category, low_priority: bool, bind_fn: *const extern "C-unwind" fn()) {
+    let _ = try_replace_acmd_script(agent, script, original, category, low_priority, AgentTarget::All, bind_fn);
+}
 
-    unsafe {
-        crate::scripts::install_live_acmd_scripts(agent, category, &mut info);
-    }
-    
+// Same as replace_acmd_script, but only installed on the regular or the `_share` agent
+#[no_mangle]
+pub extern "Rust" fn replace_acmd_script_for_target(agent: Hash40, script: Hash40, original: Option<&'static mut *const extern "C" fn()>, category: Category, low_priority: bool, target: AgentTarget, bind_fn: *const extern "C-unwind" fn()) {
+    let _ = try_replace_acmd_script(agent, script, original, category, low_priority, target, bind_fn);
+}
+
+// The names are recorded as labels, so every message about the registration can use them
+#[no_mangle]
+pub extern "Rust" fn replace_acmd_script_by_name(agent: &str, script: &str, original: Option<&'static mut *const extern "C" fn()>, category: Category, low_priority: bool, bind_fn: *const extern "C-unwind" fn()) {
+    replace_acmd_script_by_name_for_target(agent, script, original, category, low_priority, AgentTarget::All, bind_fn);
+}
+
+#[no_mangle]
+pub extern "Rust" fn replace_acmd_script_by_name_for_target(agent: &str, script: &str, original: Option<&'static mut *const extern "C" fn()>, category: Category, low_priority: bool, target: AgentTarget, bind_fn: *const extern "C-unwind" fn()) {
+    let agent = crate::labels::record(agent);
+    let script = crate::labels::record(script);
+    let _ = try_replace_acmd_script(agent, script, original, category, low_priority, target, bind_fn);
+}
+
+fn add_acmd_script(agent: Hash40, mut info: ScriptInfo, category: Category) -> Result<(), RegistrationError> {
     let mut map = match category {
         Category::ACMD_GAME => GAME_SCRIPTS.lock(),
         Category::ACMD_EFFECT => EFFECT_SCRIPTS.lock(),
         Category::ACMD_SOUND => SOUND_SCRIPTS.lock(),
         Category::ACMD_EXPRESSION => EXPRESSION_SCRIPTS.lock()
     };
+    let script_list = map.entry(agent).or_insert_with(Vec::new);
 
-    if let Some(script_list) = map.get_mut(&agent) {
-        for script_info in script_list.iter_mut() {
-            if script_info.script == info.script {
-                if script_info.low_priority {
-                    *script_info = info;
-                } else {
-                    println!("[smashline::acmd] ACMD script already replaced with high priority | Agent: {:#x}, Script: {:#x}", agent.hash, info.script.hash);
-                }
-                return;
-            } 
+    // conflicts are settled before anything is installed, so a registration that loses never reaches a live agent
+    let same_script: Vec<usize> = (0..script_list.len()).filter(|index| script_list[*index].script == info.script).collect();
+    let existing: Vec<(AgentTarget, bool)> = same_script.iter().map(|index| (script_list[*index].target, script_list[*index].low_priority)).collect();
+    let overlapping = match info.target.find_replaced(&existing) {
+        Ok(overlapping) => overlapping,
+        Err(_) => {
+            println!("[smashline::acmd] ACMD script already replaced with high priority | Script: {}", info.describe(agent));
+            info.discard_shim();
+            return Err(RegistrationError::Conflict);
+        }
+    };
+    // the ones that only lose one side stay registered for the other, back to front so the indices stay valid
+    let mut replaced = Vec::new();
+    for (index, remaining) in overlapping.iter().rev() {
+        match remaining {
+            Some(target) => script_list[same_script[*index]].target = *target,
+            None => replaced.push(script_list.remove(same_script[*index]))
+        }
+    }
+
+    {
+        let narrowed = overlapping.iter().filter(|(_, remaining)| remaining.is_some()).map(|(index, _)| &script_list[same_script[*index]]);
+        let taken_over: Vec<&ScriptInfo> = replaced.iter().chain(narrowed).collect();
+        unsafe {
+            crate::scripts::install_live_acmd_scripts(agent, category, &mut info, &taken_over);
+        }
+    }
+    for replaced in replaced.iter_mut() {
+        replaced.release_shim();
+    }
+    script_list.push(info);
+    drop(map);
+    crate::validate::registration_added();
+    Ok(())
+}
+
+// The labels of every script replaced for the agent, by name when the hash has a label
+#[no_mangle]
+pub extern "Rust" fn get_replaced_acmd_scripts(agent: Hash40, category: Category) -> Vec<String> {
+    let map = match category {
+        Category::ACMD_GAME => GAME_SCRIPTS.lock(),
+        Category::ACMD_EFFECT => EFFECT_SCRIPTS.lock(),
+        Category::ACMD_SOUND => SOUND_SCRIPTS.lock(),
+        Category::ACMD_EXPRESSION => EXPRESSION_SCRIPTS.lock()
+    };
+    match map.get(&agent) {
+        Some(script_list) => script_list.iter().map(|info| crate::labels::format_hash(info.script)).collect(),
+        None => Vec::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AgentTarget;
+
+    #[test]
+    fn targets_overlap() {
+        assert!(AgentTarget::All.overlaps(AgentTarget::Main));
+        assert!(AgentTarget::Share.overlaps(AgentTarget::All));
+        assert!(AgentTarget::Main.overlaps(AgentTarget::Main));
+        assert!(!AgentTarget::Main.overlaps(AgentTarget::Share));
+    }
+
+    #[test]
+    fn main_and_share_coexist() {
+        assert_eq!(AgentTarget::Share.find_replaced(&[(AgentTarget::Main, false)]), Ok(vec![]));
+        assert_eq!(AgentTarget::Main.find_replaced(&[(AgentTarget::Share, false)]), Ok(vec![]));
+    }
+
+    #[test]
+    fn all_replaces_main_and_share() {
+        let existing = [(AgentTarget::Main, true), (AgentTarget::Share, true)];
+        assert_eq!(AgentTarget::All.find_replaced(&existing), Ok(vec![(0, None), (1, None)]));
+    }
+
+    #[test]
+    fn all_is_rejected_by_either_high_priority_side() {
+        assert_eq!(AgentTarget::All.find_replaced(&[(AgentTarget::Main, true), (AgentTarget::Share, false)]), Err(1));
+        assert_eq!(AgentTarget::All.find_replaced(&[(AgentTarget::Main, false), (AgentTarget::Share, true)]), Err(0));
+    }
+
+    #[test]
+    fn one_side_only_takes_its_side_of_all() {
+        for (side, other_is_share) in [(AgentTarget::Main, true), (AgentTarget::Share, false)].iter() {
+            let mut targets = vec![AgentTarget::All];
+            let overlapping = side.find_replaced(&[(AgentTarget::All, true)]).unwrap();
+            assert_eq!(overlapping.len(), 1);
+            match overlapping[0] {
+                (index, Some(remaining)) => targets[index] = remaining,
+                (index, None) => { targets.remove(index); }
+            }
+            targets.push(*side);
+            // the new registration gets its side and the All one still runs on the other
+            let resolve = |is_share: bool| targets.iter().position(|target| target.matches(is_share));
+            assert_eq!(resolve(*other_is_share), Some(0));
+            assert_eq!(resolve(!*other_is_share), Some(1));
         }
-        script_list.push(info);
-    } else {
-        map.insert(agent, vec![info]);
+        assert_eq!(AgentTarget::Share.find_replaced(&[(AgentTarget::All, false)]), Err(0));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn all_replacing_all_takes_everything() {
+        assert_eq!(AgentTarget::All.find_replaced(&[(AgentTarget::All, true)]), Ok(vec![(0, None)]));
+        assert_eq!(AgentTarget::Main.find_replaced(&[(AgentTarget::Main, true)]), Ok(vec![(0, None)]));
+    }
+
+    #[test]
+    fn nothing_to_replace() {
+        assert_eq!(AgentTarget::All.find_replaced(&[]), Ok(vec![]));
+    }
+}
@@ -1,14 +1,19 @@
 use smash::lua2cpp::*;
-use smash::lib::{LuaConst, L2CValue, lua_const::{BATTLE_OBJECT_CATEGORY_FIGHTER, BATTLE_OBJECT_CATEGORY_WEAPON}};
+use smash::lib::{LuaConst, L2CValue, lua_const::{BATTLE_OBJECT_CATEGORY_FIGHTER, BATTLE_OBJECT_CATEGORY_WEAPON, LUA_SCRIPT_STATUS_FUNC_STATUS_PRE}};
 use smash::phx::Hash40;
 use smash::app::lua_bind::StatusModule;
 
 use skyline::nro::NroInfo;
+use nnsdk::root::rtld::ModuleObject;
 
 use crate::LuaConstant;
+use crate::abi::RegistrationError;
 use crate::c_str;
+use crate::rcu::Rcu;
 
 use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use parking_lot::Mutex;
 
 type FighterFrame = extern "C" fn(&mut L2CFighterCommon) -> L2CValue;
@@ -19,9 +24,39 @@ type FighterReset = fn(&mut L2CFighterCommon);
 type AgentReset = fn(&mut L2CFighterBase);
 type FighterInit = fn(&mut L2CFighterCommon);
 type AgentInit = fn(&mut L2CFighterBase);
+type StatusChangeCallback = fn(&mut L2CFighterBase, i32, i32);
+type ItemFrameCallback = fn(&mut L2CAgentBase);
+type ItemInit = fn(&mut L2CAgentBase);
+type ItemReset = fn(&mut L2CAgentBase);
+type ItemLine = extern "C" fn(&mut L2CAgentBase) -> L2CValue;
+type ItemResetLine = extern "C" fn(&mut L2CAgentBase);
 
+// Where a frame callback runs relative to the vanilla frame function
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum FramePlacement {
+    BeforeOriginal,
+    AfterOriginal
+}
+
+// Callbacks run in ascending order, ties run in registration order
+#[derive(Copy, Clone)]
+struct OrderedCallback<T: Copy> {
+    pub callback: T,
+    pub placement: FramePlacement,
+    pub order: i32
+}
+
+fn insert_ordered<T: Copy>(callbacks: &mut Vec<OrderedCallback<T>>, callback: OrderedCallback<T>) {
+    let index = callbacks.iter().position(|other| other.order > callback.order).unwrap_or(callbacks.len());
+    callbacks.insert(index, callback);
+}
+
+// Frame replacements are layered per kind, lowest priority closest to the vanilla function.
+// Each layer's `original` is relinked whenever the layers change, so removing one from the
+// middle of a chain never leaves the layer above it pointing at unloaded code.
 struct FighterFrameInfo {
     pub agent: LuaConstant,
+    pub priority: i32,
     pub original: Option<&'static mut *const extern "C" fn()>,
     pub frame: FighterFrame
 }
@@ -31,6 +66,7 @@ unsafe impl Send for FighterFrameInfo {}
 
 struct AgentFrameInfo {
     pub agent: LuaConstant,
+    pub priority: i32,
     pub original: Option<&'static mut *const extern "C" fn()>,
     pub frame: AgentFrame
 }
@@ -41,6 +77,7 @@ unsafe impl Send for AgentFrameInfo {}
 struct AgentFrameMainInfo {
     pub agent: LuaConstant,
     pub is_fighter: bool,
+    pub priority: i32,
     pub original: Option<&'static mut *const extern "C" fn()>,
     pub frame: AgentFrame
 }
@@ -48,20 +85,185 @@ struct AgentFrameMainInfo {
 unsafe impl Sync for AgentFrameMainInfo {}
 unsafe impl Send for AgentFrameMainInfo {}
 
+// Callbacks that only run for a set of kinds. The kinds can't be resolved until common is loaded,
+// so the kind index is only built once they can be
+#[derive(Clone)]
+struct KindCallbacks<T: Copy> {
+    pub registrations: Vec<(Vec<LuaConstant>, OrderedCallback<T>)>,
+    pub by_kind: HashMap<i32, Vec<OrderedCallback<T>>>,
+    pub is_resolved: bool
+}
+
+impl<T: Copy> KindCallbacks<T> {
+    pub fn new() -> Self {
+        Self {
+            registrations: Vec::new(),
+            by_kind: HashMap::new(),
+            is_resolved: false
+        }
+    }
+
+    fn index(by_kind: &mut HashMap<i32, Vec<OrderedCallback<T>>>, kinds: &mut Vec<LuaConstant>, callback: OrderedCallback<T>) {
+        for kind in kinds.iter_mut() {
+            insert_ordered(by_kind.entry(kind.get()).or_insert_with(Vec::new), callback);
+        }
+    }
+
+    pub fn add(&mut self, mut kinds: Vec<LuaConstant>, callback: OrderedCallback<T>) {
+        if self.is_resolved {
+            Self::index(&mut self.by_kind, &mut kinds, callback);
+        }
+        self.registrations.push((kinds, callback));
+    }
+
+    pub fn resolve(&mut self) {
+        let mut by_kind = HashMap::new();
+        for (kinds, callback) in self.registrations.iter_mut() {
+            Self::index(&mut by_kind, kinds, *callback);
+        }
+        self.by_kind = by_kind;
+        self.is_resolved = true;
+    }
+
+    pub fn get(&self, kind: i32) -> &[OrderedCallback<T>] {
+        self.by_kind.get(&kind).map(|callbacks| callbacks.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn retain<F: Fn(&T) -> bool>(&mut self, keep: F) {
+        self.registrations.retain(|(_, callback)| keep(&callback.callback));
+        for callbacks in self.by_kind.values_mut() {
+            callbacks.retain(|callback| keep(&callback.callback));
+        }
+    }
+}
+
+unsafe impl<T: Copy> Sync for KindCallbacks<T> {}
+unsafe impl<T: Copy> Send for KindCallbacks<T> {}
+
+#[derive(Clone)]
+struct StatusChangeInfo {
+    pub kind: Option<LuaConstant>,
+    pub is_fighter: bool,
+    pub from: Option<LuaConstant>,
+    pub to: Option<LuaConstant>,
+    pub callback: StatusChangeCallback
+}
+
+// StatusChangeInfo with its constants evaluated, which can only happen once common is loaded
+#[derive(Copy, Clone)]
+struct StatusChangeFilter {
+    pub kind: Option<i32>,
+    pub is_fighter: bool,
+    pub from: Option<i32>,
+    pub to: Option<i32>,
+    pub callback: StatusChangeCallback
+}
+
+impl StatusChangeFilter {
+    pub fn matches(&self, is_fighter: bool, kind: i32, from: i32, to: i32) -> bool {
+        self.is_fighter == is_fighter
+        && self.kind.map_or(true, |filter| filter == kind)
+        && self.from.map_or(true, |filter| filter == from)
+        && self.to.map_or(true, |filter| filter == to)
+    }
+}
+
+#[derive(Clone)]
+struct StatusChangeCallbacks {
+    pub registrations: Vec<StatusChangeInfo>,
+    pub filters: Vec<StatusChangeFilter>,
+    pub is_resolved: bool
+}
+
+impl StatusChangeCallbacks {
+    pub fn new() -> Self {
+        Self {
+            registrations: Vec::new(),
+            filters: Vec::new(),
+            is_resolved: false
+        }
+    }
+
+    fn evaluate(info: &mut StatusChangeInfo) -> StatusChangeFilter {
+        StatusChangeFilter {
+            kind: info.kind.as_mut().map(|kind| kind.get()),
+            is_fighter: info.is_fighter,
+            from: info.from.as_mut().map(|from| from.get()),
+            to: info.to.as_mut().map(|to| to.get()),
+            callback: info.callback
+        }
+    }
+
+    pub fn add(&mut self, mut info: StatusChangeInfo) {
+        if self.is_resolved {
+            self.filters.push(Self::evaluate(&mut info));
+        }
+        self.registrations.push(info);
+    }
+
+    pub fn resolve(&mut self) {
+        self.filters = self.registrations.iter_mut().map(Self::evaluate).collect();
+        self.is_resolved = true;
+    }
+
+    pub fn retain<F: Fn(StatusChangeCallback) -> bool>(&mut self, keep: F) {
+        self.registrations.retain(|info| keep(info.callback));
+        self.filters.retain(|filter| keep(filter.callback));
+    }
+}
+
+unsafe impl Sync for StatusChangeCallbacks {}
+unsafe impl Send for StatusChangeCallbacks {}
+
 lazy_static! {
     static ref FIGHTER_FRAMES: Mutex<Vec<FighterFrameInfo>> = Mutex::new(Vec::new());
     static ref WEAPON_FRAMES: Mutex<Vec<AgentFrameInfo>> = Mutex::new(Vec::new());
     static ref AGENT_FRAMES_MAIN: Mutex<Vec<AgentFrameMainInfo>> = Mutex::new(Vec::new());
 
+    // outermost layer of each chain, looked up by the dispatchers every frame
+    static ref FIGHTER_FRAME_CHAINS: Rcu<HashMap<i32, usize>> = Rcu::new(HashMap::new());
+    static ref WEAPON_FRAME_CHAINS: Rcu<HashMap<i32, usize>> = Rcu::new(HashMap::new());
+    static ref AGENT_FRAME_MAIN_CHAINS: Rcu<HashMap<(bool, i32), usize>> = Rcu::new(HashMap::new());
+
     static ref FIGHTER_RESETS: Mutex<Vec<FighterReset>> = Mutex::new(Vec::new());
     static ref AGENT_RESETS: Mutex<Vec<AgentReset>> = Mutex::new(Vec::new());
 
-    static ref FIGHTER_FRAME_CALLBACKS: Mutex<Vec<FighterFrameCallback>> = Mutex::new(Vec::new());
-    static ref WEAPON_FRAME_CALLBACKS: Mutex<Vec<AgentFrameCallback>> = Mutex::new(Vec::new());
-    static ref AGENT_FRAME_MAIN_CALLBACKS: Mutex<Vec<AgentFrameCallback>> = Mutex::new(Vec::new());
+    // these are read every frame, see rcu.rs
+    static ref FIGHTER_FRAME_CALLBACKS: Rcu<Vec<OrderedCallback<FighterFrameCallback>>> = Rcu::new(Vec::new());
+    static ref WEAPON_FRAME_CALLBACKS: Rcu<Vec<OrderedCallback<AgentFrameCallback>>> = Rcu::new(Vec::new());
+    static ref AGENT_FRAME_MAIN_CALLBACKS: Rcu<Vec<OrderedCallback<AgentFrameCallback>>> = Rcu::new(Vec::new());
+
+    static ref FIGHTER_KIND_FRAME_CALLBACKS: Rcu<KindCallbacks<FighterFrameCallback>> = Rcu::new(KindCallbacks::new());
+    static ref WEAPON_KIND_FRAME_CALLBACKS: Rcu<KindCallbacks<AgentFrameCallback>> = Rcu::new(KindCallbacks::new());
+    static ref FIGHTER_KIND_FRAME_MAIN_CALLBACKS: Rcu<KindCallbacks<AgentFrameCallback>> = Rcu::new(KindCallbacks::new());
+    static ref WEAPON_KIND_FRAME_MAIN_CALLBACKS: Rcu<KindCallbacks<AgentFrameCallback>> = Rcu::new(KindCallbacks::new());
+
+    static ref STATUS_CHANGE_CALLBACKS: Rcu<StatusChangeCallbacks> = Rcu::new(StatusChangeCallbacks::new());
+    // the pre function of every status on each status agent that status_pre_dispatch was put in front of,
+    // by agent and then status kind. Set up when the recreated status vtables in scripts.rs set the status
+    // scripts and dropped when the agent is destroyed
+    static ref STATUS_AGENT_PRES: Mutex<HashMap<usize, HashMap<i32, usize>>> = Mutex::new(HashMap::new());
 
     static ref FIGHTER_INIT_CALLBACKS: Mutex<Vec<FighterInit>> = Mutex::new(Vec::new());
     static ref AGENT_INIT_CALLBACKS: Mutex<Vec<AgentInit>> = Mutex::new(Vec::new());
+
+    // items don't go through L2CFighterBase, so they get their own lists
+    static ref ITEM_FRAME_MAIN_CALLBACKS: Rcu<Vec<OrderedCallback<ItemFrameCallback>>> = Rcu::new(Vec::new());
+    static ref ITEM_KIND_FRAME_MAIN_CALLBACKS: Rcu<KindCallbacks<ItemFrameCallback>> = Rcu::new(KindCallbacks::new());
+    static ref ITEM_INIT_CALLBACKS: Rcu<Vec<OrderedCallback<ItemInit>>> = Rcu::new(Vec::new());
+    static ref ITEM_KIND_INIT_CALLBACKS: Rcu<KindCallbacks<ItemInit>> = Rcu::new(KindCallbacks::new());
+    static ref ITEM_RESET_CALLBACKS: Rcu<Vec<OrderedCallback<ItemReset>>> = Rcu::new(Vec::new());
+    static ref ITEM_KIND_RESET_CALLBACKS: Rcu<KindCallbacks<ItemReset>> = Rcu::new(KindCallbacks::new());
+}
+
+static mut ITEM_FRAME_MAIN_ORIGINAL: *const extern "C" fn() = 0 as _;
+static mut ITEM_INIT_ORIGINAL: *const extern "C" fn() = 0 as _;
+static mut ITEM_RESET_ORIGINAL: *const extern "C" fn() = 0 as _;
+static SHOULD_INSTALL_ITEM_CB: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    // the loaded item module's ModuleObject, and whether its system lines have been hooked yet
+    static ref ITEM_MODULE: Mutex<Option<(usize, bool)>> = Mutex::new(None);
 }
 
 static mut SHOULD_INSTALL_FIGHTER_CB: bool = false;
@@ -85,6 +287,117 @@ extern "C" {
     fn call_calc_param();
 }
 
+static VANILLA_FIGHTER_FRAME: AtomicUsize = AtomicUsize::new(0);
+static VANILLA_AGENT_FRAME: AtomicUsize = AtomicUsize::new(0);
+static VANILLA_AGENT_FRAME_MAIN: AtomicUsize = AtomicUsize::new(0);
+
+// already been resolved by the time we need them, so we have to do it this way unfortunately :(
+fn vanilla_fighter_frame() -> usize {
+    if VANILLA_FIGHTER_FRAME.load(Ordering::Acquire) == 0 {
+        let mut sys_line_system_control = 0usize;
+        unsafe { skyline::nn::ro::LookupSymbol(&mut sys_line_system_control, c_str!("_ZN7lua2cpp16L2CFighterCommon31sys_line_system_control_fighterEv")); }
+        VANILLA_FIGHTER_FRAME.store(sys_line_system_control, Ordering::Release);
+    }
+    VANILLA_FIGHTER_FRAME.load(Ordering::Acquire)
+}
+
+fn vanilla_agent_frame() -> usize {
+    if VANILLA_AGENT_FRAME.load(Ordering::Acquire) == 0 {
+        let mut sys_line_system_control = 0usize;
+        unsafe { skyline::nn::ro::LookupSymbol(&mut sys_line_system_control, c_str!("_ZN7lua2cpp14L2CFighterBase23sys_line_system_controlEv")); }
+        VANILLA_AGENT_FRAME.store(sys_line_system_control, Ordering::Release);
+    }
+    VANILLA_AGENT_FRAME.load(Ordering::Acquire)
+}
+
+fn vanilla_agent_frame_main() -> usize {
+    if VANILLA_AGENT_FRAME_MAIN.load(Ordering::Acquire) == 0 {
+        let mut sys_line_status_system_control = 0usize;
+        unsafe { skyline::nn::ro::LookupSymbol(&mut sys_line_status_system_control, c_str!("_ZN7lua2cpp14L2CFighterBase30sys_line_status_system_controlEv")); }
+        VANILLA_AGENT_FRAME_MAIN.store(sys_line_status_system_control, Ordering::Release);
+    }
+    VANILLA_AGENT_FRAME_MAIN.load(Ordering::Acquire)
+}
+
+// Points each layer's original at the layer below it, innermost first, and returns the outermost layer of each chain
+fn link_chains<'a, K: Hash + Eq>(layers: impl Iterator<Item = (K, &'a mut Option<&'static mut *const extern "C" fn()>, usize)>, vanilla: usize) -> HashMap<K, usize> {
+    let mut heads = HashMap::new();
+    for (key, original, frame) in layers {
+        let below = *heads.get(&key).unwrap_or(&vanilla);
+        if let Some(original) = original.as_mut() {
+            **original = below as *const extern "C" fn();
+        }
+        heads.insert(key, frame);
+    }
+    heads
+}
+
+// Same ordering as the frame callbacks, later registrations of the same priority wrap earlier ones
+fn insert_by_priority<T>(layers: &mut Vec<T>, layer: T, priority_of: impl Fn(&T) -> i32) {
+    let priority = priority_of(&layer);
+    let index = layers.iter().position(|other| priority_of(other) > priority).unwrap_or(layers.len());
+    layers.insert(index, layer);
+}
+
+fn relink_fighter_frames() {
+    if unsafe { crate::COMMON_MEMORY_INFO.is_none() } {
+        return;
+    }
+    let mut fighter_frames = FIGHTER_FRAMES.lock();
+    let layers = fighter_frames.iter_mut().map(|info| (info.agent.get(), &mut info.original, info.frame as *const () as usize));
+    let heads = link_chains(layers, vanilla_fighter_frame());
+    FIGHTER_FRAME_CHAINS.update(|chains| *chains = heads);
+}
+
+fn relink_weapon_frames() {
+    if unsafe { crate::COMMON_MEMORY_INFO.is_none() } {
+        return;
+    }
+    let mut weapon_frames = WEAPON_FRAMES.lock();
+    let layers = weapon_frames.iter_mut().map(|info| (info.agent.get(), &mut info.original, info.frame as *const () as usize));
+    let heads = link_chains(layers, vanilla_agent_frame());
+    WEAPON_FRAME_CHAINS.update(|chains| *chains = heads);
+}
+
+fn relink_agent_frames_main() {
+    if unsafe { crate::COMMON_MEMORY_INFO.is_none() } {
+        return;
+    }
+    let mut agent_frames_main = AGENT_FRAMES_MAIN.lock();
+    let layers = agent_frames_main.iter_mut().map(|info| ((info.is_fighter, info.agent.get()), &mut info.original, info.frame as *const () as usize));
+    let heads = link_chains(layers, vanilla_agent_frame_main());
+    AGENT_FRAME_MAIN_CHAINS.update(|chains| *chains = heads);
+}
+
+// Every agent gets shifted into one of these instead of the top of its chain, so layers can be
+// added or removed (e.g. by reloading the development plugin) without touching live agents
+unsafe extern "C" fn fighter_frame_dispatch(fighter: &mut L2CFighterCommon) -> L2CValue {
+    let kind = smash::app::utility::get_kind(&mut *fighter.module_accessor);
+    let head = FIGHTER_FRAME_CHAINS.read().get(&kind).copied().unwrap_or_else(vanilla_fighter_frame);
+    let func: FighterFrame = std::mem::transmute(head);
+    func(fighter)
+}
+
+unsafe extern "C" fn weapon_frame_dispatch(agent: &mut L2CFighterBase) -> L2CValue {
+    let kind = smash::app::utility::get_kind(&mut *agent.module_accessor);
+    let head = WEAPON_FRAME_CHAINS.read().get(&kind).copied().unwrap_or_else(vanilla_agent_frame);
+    let func: AgentFrame = std::mem::transmute(head);
+    func(agent)
+}
+
+unsafe extern "C" fn agent_frame_main_dispatch(agent: &mut L2CFighterBase) -> L2CValue {
+    let category = smash::app::utility::get_category(&mut *agent.module_accessor);
+    let kind = smash::app::utility::get_kind(&mut *agent.module_accessor);
+    let key = (category == *BATTLE_OBJECT_CATEGORY_FIGHTER, kind);
+    let head = if category == *BATTLE_OBJECT_CATEGORY_FIGHTER || category == *BATTLE_OBJECT_CATEGORY_WEAPON {
+        AGENT_FRAME_MAIN_CHAINS.read().get(&key).copied()
+    } else {
+        None
+    };
+    let func: AgentFrame = std::mem::transmute(head.unwrap_or_else(vanilla_agent_frame_main));
+    func(agent)
+}
+
 #[skyline::hook(replace = L2CFighterCommon_sys_line_system_init)]
 unsafe extern "C" fn sys_line_system_fighter_init_replace(fighter: &mut L2CFighterCommon) -> L2CValue {
     use std::mem::transmute;
@@ -100,25 +413,8 @@ unsafe extern "C" fn sys_line_system_fighter_init_replace(fighter: &mut L2CFight
     fighter.sv_set_function_hash(transmute(call_notify_event_gimmick as *const ()), Hash40::new("call_notify_event_gimmick"));
     fighter.sv_set_function_hash(transmute(call_calc_param as *const ()), Hash40::new("call_calc_param"));
 
-    // already been resolved by this point, so we have to do it this way unfortunately :(
-    let mut sys_line_system_control = 0usize;
-    skyline::nn::ro::LookupSymbol(&mut sys_line_system_control, c_str!("_ZN7lua2cpp16L2CFighterCommon31sys_line_system_control_fighterEv"));
-
-    let mut system_control = L2CValue::Ptr(transmute(sys_line_system_control as *const ()));
-    let mut fighter_frames = FIGHTER_FRAMES.lock();
-    for frame_info in fighter_frames.iter_mut() {
-        if frame_info.agent.get() == smash::app::utility::get_kind(&mut *fighter.module_accessor) {
-            if let Some(original) = frame_info.original.as_mut() {
-                let og = system_control.get_ptr() as *const extern "C" fn();
-                **original = og;
-            }
-            system_control = L2CValue::Ptr(transmute(frame_info.frame as *const ()));
-        }
-    }
-    drop(fighter_frames);
-    fighter.shift(system_control.clone());
-    let func: extern "C" fn(&mut L2CFighterCommon) -> L2CValue = transmute(system_control.get_ptr());
-    func(fighter)
+    fighter.shift(L2CValue::Ptr(transmute(fighter_frame_dispatch as *const ())));
+    fighter_frame_dispatch(fighter)
 }
 
 #[skyline::hook(replace = L2CFighterBase_sys_line_system_init)]
@@ -129,23 +425,8 @@ unsafe extern "C" fn sys_line_system_init_replace(agent: &mut L2CFighterBase) ->
         callback(agent);
     }
 
-    let mut sys_line_system_control = 0usize;
-    skyline::nn::ro::LookupSymbol(&mut sys_line_system_control, c_str!("_ZN7lua2cpp14L2CFighterBase23sys_line_system_controlEv"));
-    let mut system_control = L2CValue::Ptr(transmute(sys_line_system_control as *const ()));
-    let mut weapon_frames = WEAPON_FRAMES.lock();
-    for frame_info in weapon_frames.iter_mut() {
-        if frame_info.agent.get() == smash::app::utility::get_kind(&mut *agent.module_accessor) {
-            if let Some(original) = frame_info.original.as_mut() {
-                let og = system_control.get_ptr() as *const extern "C" fn();
-                **original = og;
-            }
-            system_control = L2CValue::Ptr(transmute(frame_info.frame as *const ()));
-        }
-    }
-    drop(weapon_frames);
-    agent.shift(system_control.clone());
-    let func: extern "C" fn(&mut L2CFighterBase) -> L2CValue = transmute(system_control.get_ptr());
-    func(agent)
+    agent.shift(L2CValue::Ptr(transmute(weapon_frame_dispatch as *const ())));
+    weapon_frame_dispatch(agent)
 }
 
 #[skyline::hook(replace = L2CFighterBase_sys_line_status_system_init)]
@@ -156,31 +437,13 @@ unsafe extern "C" fn sys_line_status_system_init_replace(agent: &mut L2CFighterB
         callback(agent);
     }
 
-    let mut sys_line_status_system_control = 0usize;
-    skyline::nn::ro::LookupSymbol(&mut sys_line_status_system_control, c_str!("_ZN7lua2cpp14L2CFighterBase30sys_line_status_system_controlEv"));
-    let mut status_system_control = L2CValue::Ptr(transmute(sys_line_status_system_control as *const ()));
-    let mut agent_frames_main = AGENT_FRAMES_MAIN.lock();
-    for frame_info in agent_frames_main.iter_mut() {
-        if (frame_info.is_fighter && smash::app::utility::get_category(&mut *agent.module_accessor) == *BATTLE_OBJECT_CATEGORY_FIGHTER)
-        || (!frame_info.is_fighter && smash::app::utility::get_category(&mut *agent.module_accessor) == *BATTLE_OBJECT_CATEGORY_WEAPON)
-        {
-            if frame_info.agent.get() == smash::app::utility::get_kind(&mut *agent.module_accessor) {
-                if let Some(original) = frame_info.original.as_mut() {
-                    let og = status_system_control.get_ptr() as *const extern "C" fn();
-                    **original = og;
-                }
-                status_system_control = L2CValue::Ptr(transmute(frame_info.frame as *const ()));
-            }
-        }
-    }
-    drop(agent_frames_main);
-    agent.shift(status_system_control.clone());
-    let func: extern "C" fn(&mut L2CFighterBase) -> L2CValue = transmute(status_system_control.get_ptr());
-    func(agent)
+    agent.shift(L2CValue::Ptr(transmute(agent_frame_main_dispatch as *const ())));
+    agent_frame_main_dispatch(agent)
 }
 
 #[skyline::hook(replace = L2CFighterCommon_RESET)]
 fn fighter_reset(fighter: &mut L2CFighterCommon) {
+    crate::storage::clear_agent_storage(unsafe { (*fighter.battle_object).battle_object_id });
     for callback in FIGHTER_RESETS.lock().iter() {
         callback(fighter)
     }
@@ -189,43 +452,296 @@ fn fighter_reset(fighter: &mut L2CFighterCommon) {
 
 #[skyline::hook(replace = L2CFighterBase_RESET)]
 fn agent_reset(agent: &mut L2CFighterBase) {
+    crate::storage::clear_agent_storage(unsafe { (*agent.battle_object).battle_object_id });
     for callback in AGENT_RESETS.lock().iter() {
         callback(agent)
     }
     original!()(agent)
 }
 
+// The item module's equivalents of the L2CFighterBase system lines. The originals are cleared when the item
+// module unloads
+unsafe extern "C" fn item_frame_main_callbacks(agent: &mut L2CAgentBase) -> L2CValue {
+    if ITEM_FRAME_MAIN_ORIGINAL.is_null() {
+        return L2CValue::I32(0);
+    }
+    let original: ItemLine = std::mem::transmute(ITEM_FRAME_MAIN_ORIGINAL);
+    let address_of = |cb: ItemFrameCallback| cb as *const () as usize;
+    let kind = smash::app::utility::get_kind(&mut *agent.module_accessor);
+    let global = ITEM_FRAME_MAIN_CALLBACKS.read();
+    let by_kind = ITEM_KIND_FRAME_MAIN_CALLBACKS.read();
+
+    let mut panicked = Vec::new();
+    if !StatusModule::is_changing(agent.module_accessor) {
+        panicked = call_ordered(global.as_slice(), by_kind.get(kind), FramePlacement::BeforeOriginal, address_of, |cb| cb(agent));
+    }
+    let ret = original(agent);
+    if !StatusModule::is_changing(agent.module_accessor) {
+        panicked.extend(call_ordered(global.as_slice(), by_kind.get(kind), FramePlacement::AfterOriginal, address_of, |cb| cb(agent)));
+    }
+    drop(global);
+    drop(by_kind);
+    remove_panicked(&ITEM_FRAME_MAIN_CALLBACKS, &ITEM_KIND_FRAME_MAIN_CALLBACKS, panicked, address_of);
+    ret
+}
+
+unsafe extern "C" fn item_init_callbacks(agent: &mut L2CAgentBase) -> L2CValue {
+    let address_of = |cb: ItemInit| cb as *const () as usize;
+    let kind = smash::app::utility::get_kind(&mut *agent.module_accessor);
+    let global = ITEM_INIT_CALLBACKS.read();
+    let by_kind = ITEM_KIND_INIT_CALLBACKS.read();
+    let mut panicked = call_ordered(global.as_slice(), by_kind.get(kind), FramePlacement::BeforeOriginal, address_of, |cb| cb(agent));
+    let ret = if ITEM_INIT_ORIGINAL.is_null() {
+        L2CValue::I32(0)
+    } else {
+        let original: ItemLine = std::mem::transmute(ITEM_INIT_ORIGINAL);
+        original(agent)
+    };
+    panicked.extend(call_ordered(global.as_slice(), by_kind.get(kind), FramePlacement::AfterOriginal, address_of, |cb| cb(agent)));
+    drop(global);
+    drop(by_kind);
+    remove_panicked(&ITEM_INIT_CALLBACKS, &ITEM_KIND_INIT_CALLBACKS, panicked, address_of);
+    ret
+}
+
+unsafe extern "C" fn item_reset_callbacks(agent: &mut L2CAgentBase) {
+    crate::storage::clear_agent_storage((*agent.battle_object).battle_object_id);
+    let address_of = |cb: ItemReset| cb as *const () as usize;
+    let kind = smash::app::utility::get_kind(&mut *agent.module_accessor);
+    let global = ITEM_RESET_CALLBACKS.read();
+    let by_kind = ITEM_KIND_RESET_CALLBACKS.read();
+    let mut panicked = call_ordered(global.as_slice(), by_kind.get(kind), FramePlacement::BeforeOriginal, address_of, |cb| cb(agent));
+    if !ITEM_RESET_ORIGINAL.is_null() {
+        let original: ItemResetLine = std::mem::transmute(ITEM_RESET_ORIGINAL);
+        original(agent);
+    }
+    panicked.extend(call_ordered(global.as_slice(), by_kind.get(kind), FramePlacement::AfterOriginal, address_of, |cb| cb(agent)));
+    drop(global);
+    drop(by_kind);
+    remove_panicked(&ITEM_RESET_CALLBACKS, &ITEM_KIND_RESET_CALLBACKS, panicked, address_of);
+}
+
+// Runs the callbacks for one side of the original function, merging the global and per-kind lists by order.
+// Returns the addresses of the ones that panicked so they can be removed
+fn call_ordered<T: Copy>(
+    global: &[OrderedCallback<T>],
+    by_kind: &[OrderedCallback<T>],
+    placement: FramePlacement,
+    address_of: impl Fn(T) -> usize,
+    mut call: impl FnMut(T)
+) -> Vec<usize> {
+    let mut panicked = Vec::new();
+    let mut global = global.iter().filter(|cb| cb.placement == placement).peekable();
+    let mut by_kind = by_kind.iter().filter(|cb| cb.placement == placement).peekable();
+    loop {
+        // global callbacks win ties, same as before kinds could be ordered
+        let take_kind = match (global.peek(), by_kind.peek()) {
+            (Some(global), Some(by_kind)) => by_kind.order < global.order,
+            (Some(_), None) => false,
+            (None, Some(_)) => true,
+            (None, None) => break
+        };
+        let callback = if take_kind { by_kind.next() } else { global.next() }.unwrap().callback;
+        let address = address_of(callback);
+        if !crate::shims::call_guarded(address, || call(callback)) {
+            panicked.push(address);
+        }
+    }
+    panicked
+}
+
+fn remove_panicked<T: Copy>(
+    global: &Rcu<Vec<OrderedCallback<T>>>,
+    by_kind: &Rcu<KindCallbacks<T>>,
+    panicked: Vec<usize>,
+    address_of: impl Fn(T) -> usize
+) {
+    if panicked.is_empty() {
+        return;
+    }
+    global.update(|callbacks| callbacks.retain(|cb| !panicked.contains(&address_of(cb.callback))));
+    by_kind.update(|callbacks| callbacks.retain(|cb| !panicked.contains(&address_of(*cb))));
+}
+
 #[skyline::hook(replace = L2CFighterCommon_sys_line_system_control_fighter)]
 fn fighter_frame_callbacks(fighter: &mut L2CFighterCommon) -> L2CValue {
+    let address_of = |cb: FighterFrameCallback| cb as *const () as usize;
+    let kind = unsafe { smash::app::utility::get_kind(&mut *fighter.module_accessor) };
+    let global = FIGHTER_FRAME_CALLBACKS.read();
+    let by_kind = FIGHTER_KIND_FRAME_CALLBACKS.read();
+    let mut panicked = call_ordered(global.as_slice(), by_kind.get(kind), FramePlacement::BeforeOriginal, address_of, |cb| cb(fighter));
     let ret = call_original!(fighter);
-    for cb in FIGHTER_FRAME_CALLBACKS.lock().iter() {
-        cb(fighter);
-    }
+    panicked.extend(call_ordered(global.as_slice(), by_kind.get(kind), FramePlacement::AfterOriginal, address_of, |cb| cb(fighter)));
+    drop(global);
+    drop(by_kind);
+    remove_panicked(&FIGHTER_FRAME_CALLBACKS, &FIGHTER_KIND_FRAME_CALLBACKS, panicked, address_of);
     ret
 }
 
 #[skyline::hook(replace = L2CFighterBase_sys_line_system_control)]
 fn weapon_frame_callbacks(weapon: &mut L2CFighterBase) -> L2CValue {
+    let address_of = |cb: AgentFrameCallback| cb as *const () as usize;
+    let kind = unsafe { smash::app::utility::get_kind(&mut *weapon.module_accessor) };
+    let global = WEAPON_FRAME_CALLBACKS.read();
+    let by_kind = WEAPON_KIND_FRAME_CALLBACKS.read();
+    let mut panicked = call_ordered(global.as_slice(), by_kind.get(kind), FramePlacement::BeforeOriginal, address_of, |cb| cb(weapon));
     let ret = call_original!(weapon);
-    for cb in WEAPON_FRAME_CALLBACKS.lock().iter() {
-        cb(weapon);
-    }
+    panicked.extend(call_ordered(global.as_slice(), by_kind.get(kind), FramePlacement::AfterOriginal, address_of, |cb| cb(weapon)));
+    drop(global);
+    drop(by_kind);
+    remove_panicked(&WEAPON_FRAME_CALLBACKS, &WEAPON_KIND_FRAME_CALLBACKS, panicked, address_of);
     ret
 }
 
 #[skyline::hook(replace = L2CFighterBase_sys_line_status_system_control)]
 fn agent_frame_main_callbacks(agent: &mut L2CFighterBase) -> L2CValue {
+    let address_of = |cb: AgentFrameCallback| cb as *const () as usize;
+    let (category, kind) = unsafe {
+        (
+            smash::app::utility::get_category(&mut *agent.module_accessor),
+            smash::app::utility::get_kind(&mut *agent.module_accessor)
+        )
+    };
+    let kind_callbacks = if category == *BATTLE_OBJECT_CATEGORY_FIGHTER {
+        Some(&*FIGHTER_KIND_FRAME_MAIN_CALLBACKS)
+    } else if category == *BATTLE_OBJECT_CATEGORY_WEAPON {
+        Some(&*WEAPON_KIND_FRAME_MAIN_CALLBACKS)
+    } else {
+        None
+    };
+    let global = AGENT_FRAME_MAIN_CALLBACKS.read();
+    let by_kind = kind_callbacks.map(|callbacks| callbacks.read());
+    let by_kind_slice = by_kind.as_ref().map(|callbacks| callbacks.get(kind)).unwrap_or(&[]);
+
+    let mut panicked = Vec::new();
+    if unsafe { !StatusModule::is_changing(agent.module_accessor) } {
+        panicked = call_ordered(global.as_slice(), by_kind_slice, FramePlacement::BeforeOriginal, address_of, |cb| cb(agent));
+    }
     let ret = call_original!(agent);
-    unsafe {
-        if !StatusModule::is_changing(agent.module_accessor) {
-            for cb in AGENT_FRAME_MAIN_CALLBACKS.lock().iter() {
-                cb(agent);
+    if unsafe { !StatusModule::is_changing(agent.module_accessor) } {
+        panicked.extend(call_ordered(global.as_slice(), by_kind_slice, FramePlacement::AfterOriginal, address_of, |cb| cb(agent)));
+    }
+    drop(by_kind);
+    drop(global);
+    if !panicked.is_empty() {
+        AGENT_FRAME_MAIN_CALLBACKS.update(|callbacks| callbacks.retain(|cb| !panicked.contains(&address_of(cb.callback))));
+        // a panicking callback can be registered for fighters and weapons, so clean up both
+        FIGHTER_KIND_FRAME_MAIN_CALLBACKS.update(|callbacks| callbacks.retain(|cb| !panicked.contains(&address_of(*cb))));
+        WEAPON_KIND_FRAME_MAIN_CALLBACKS.update(|callbacks| callbacks.retain(|cb| !panicked.contains(&address_of(*cb))));
+    }
+    ret
+}
+
+// Only fighters and weapons get status change callbacks, (is_fighter, kind)
+unsafe fn get_status_change_target(agent: *mut L2CAgentBase) -> Option<(bool, i32)> {
+    let module_accessor = &mut *(*agent).module_accessor;
+    let category = smash::app::utility::get_category(module_accessor);
+    let kind = smash::app::utility::get_kind(module_accessor);
+    if category == *BATTLE_OBJECT_CATEGORY_FIGHTER {
+        Some((true, kind))
+    } else if category == *BATTLE_OBJECT_CATEGORY_WEAPON {
+        Some((false, kind))
+    } else {
+        None
+    }
+}
+
+// Puts status_pre_dispatch in front of every status's pre function, the pre function is the first thing
+// the game runs when an agent changes status. Called once the recreated status vtable has set the agent's
+// status scripts, and on live agents when the first status change callback is registered
+pub unsafe fn hook_status_changes(agent: *mut L2CAgentBase) {
+    if STATUS_CHANGE_CALLBACKS.read().registrations.is_empty() || get_status_change_target(agent).is_none() {
+        return;
+    }
+    let mut pres = STATUS_AGENT_PRES.lock();
+    if pres.contains_key(&(agent as usize)) {
+        return;
+    }
+    let condition = *LUA_SCRIPT_STATUS_FUNC_STATUS_PRE;
+    let mut originals = HashMap::new();
+    for status in crate::catalogue::read_status_kinds(agent, condition) {
+        let original = (*agent).sv_get_status_func(&L2CValue::I32(status), &L2CValue::I32(condition)).get_ptr() as usize;
+        originals.insert(status, original);
+        (*agent).sv_set_status_func(
+            L2CValue::I32(status),
+            L2CValue::I32(condition),
+            std::mem::transmute(status_pre_dispatch as *const extern "C" fn())
+        );
+    }
+    pres.insert(agent as usize, originals);
+}
+
+pub fn unhook_status_changes(agent: *mut L2CAgentBase) {
+    STATUS_AGENT_PRES.lock().remove(&(agent as usize));
+}
+
+// Live installs and removals go through these two instead of sv_get_status_func/sv_set_status_func, so that
+// they see and replace the pre function underneath status_pre_dispatch rather than the dispatcher itself
+pub unsafe fn get_status_func(agent: *mut L2CAgentBase, status: i32, condition: i32) -> usize {
+    if condition == *LUA_SCRIPT_STATUS_FUNC_STATUS_PRE {
+        if let Some(original) = STATUS_AGENT_PRES.lock().get(&(agent as usize)).and_then(|originals| originals.get(&status)) {
+            return *original;
+        }
+    }
+    (*agent).sv_get_status_func(&L2CValue::I32(status), &L2CValue::I32(condition)).get_ptr() as usize
+}
+
+pub unsafe fn set_status_func(agent: *mut L2CAgentBase, status: i32, condition: i32, func: usize) {
+    let mut installed = func;
+    if condition == *LUA_SCRIPT_STATUS_FUNC_STATUS_PRE {
+        if let Some(originals) = STATUS_AGENT_PRES.lock().get_mut(&(agent as usize)) {
+            if func == 0 {
+                originals.remove(&status);
+            } else {
+                originals.insert(status, func);
+                installed = status_pre_dispatch as *const () as usize;
             }
         }
     }
+    (*agent).sv_set_status_func(L2CValue::I32(status), L2CValue::I32(condition), std::mem::transmute(installed));
+}
+
+unsafe extern "C" fn status_pre_dispatch(agent: &mut L2CFighterBase) -> L2CValue {
+    // by the time the pre function runs the status module has already moved on to the new status
+    let to = StatusModule::status_kind(agent.module_accessor);
+    let from = StatusModule::prev_status_kind(agent.module_accessor, 0);
+    let original = STATUS_AGENT_PRES.lock().get(&(agent as *mut L2CFighterBase as usize)).and_then(|originals| originals.get(&to).copied());
+    let ret = match original {
+        Some(original) => {
+            let callable: extern "C" fn(&mut L2CFighterBase) -> L2CValue = std::mem::transmute(original);
+            callable(agent)
+        },
+        None => L2CValue::I32(0)
+    };
+    status_change_callbacks(agent, from, to);
     ret
 }
 
+fn status_change_callbacks(agent: &mut L2CFighterBase, from: i32, to: i32) {
+    let (is_fighter, kind) = match unsafe { get_status_change_target(agent as *mut L2CFighterBase as *mut L2CAgentBase) } {
+        Some(target) => target,
+        None => return
+    };
+    let address_of = |cb: StatusChangeCallback| cb as *const () as usize;
+    let mut panicked = Vec::new();
+    let callbacks = STATUS_CHANGE_CALLBACKS.read();
+    for filter in callbacks.filters.iter().filter(|filter| filter.matches(is_fighter, kind, from, to)) {
+        let callback = filter.callback;
+        if !crate::shims::call_guarded(address_of(callback), || callback(agent, from, to)) {
+            panicked.push(address_of(callback));
+        }
+    }
+    drop(callbacks);
+    if !panicked.is_empty() {
+        STATUS_CHANGE_CALLBACKS.update(|callbacks| callbacks.retain(|cb| !panicked.contains(&address_of(cb))));
+    }
+}
+
+pub fn remove_status_change_callbacks(range: (usize, usize)) {
+    let range = range.0..range.1;
+    STATUS_CHANGE_CALLBACKS.update(|callbacks| callbacks.retain(|cb| !range.contains(&(cb as *const () as usize))));
+}
+
 pub fn remove_fighter_resets(range: (usize, usize)) {
     let (begin, end) = range;
     let mut resets = FIGHTER_RESETS.lock();
@@ -254,38 +770,35 @@ pub fn remove_agent_resets(range: (usize, usize)) {
 
 pub fn remove_fighter_frame_callbacks(range: (usize, usize)) {
     let range = range.0..range.1;
-    let mut callbacks = FIGHTER_FRAME_CALLBACKS.lock();
-    let mut new_callbacks = Vec::with_capacity(callbacks.len());
-    for callback in callbacks.iter() {
-        if !range.contains(&(*callback as *const () as usize)) {
-            new_callbacks.push(*callback);
-        }
-    }
-    *callbacks = new_callbacks;
+    FIGHTER_FRAME_CALLBACKS.update(|callbacks| callbacks.retain(|callback| !range.contains(&(callback.callback as *const () as usize))));
 }
 
 pub fn remove_weapon_frame_callbacks(range: (usize, usize)) {
     let range = range.0..range.1;
-    let mut callbacks = WEAPON_FRAME_CALLBACKS.lock();
-    let mut new_callbacks = Vec::with_capacity(callbacks.len());
-    for callback in callbacks.iter() {
-        if !range.contains(&(*callback as *const () as usize)) {
-            new_callbacks.push(*callback);
-        }
-    }
-    *callbacks = new_callbacks;
+    WEAPON_FRAME_CALLBACKS.update(|callbacks| callbacks.retain(|callback| !range.contains(&(callback.callback as *const () as usize))));
 }
 
 pub fn remove_agent_frame_main_callbacks(range: (usize, usize)) {
     let range = range.0..range.1;
-    let mut callbacks = AGENT_FRAME_MAIN_CALLBACKS.lock();
-    let mut new_callbacks = Vec::with_capacity(callbacks.len());
-    for callback in callbacks.iter() {
-        if !range.contains(&(*callback as *const () as usize)) {
-            new_callbacks.push(*callback);
-        }
-    }
-    *callbacks = new_callbacks;
+    AGENT_FRAME_MAIN_CALLBACKS.update(|callbacks| callbacks.retain(|callback| !range.contains(&(callback.callback as *const () as usize))));
+}
+
+pub fn remove_kind_frame_callbacks(range: (usize, usize)) {
+    let range = range.0..range.1;
+    FIGHTER_KIND_FRAME_CALLBACKS.update(|callbacks| callbacks.retain(|cb| !range.contains(&(*cb as *const () as usize))));
+    WEAPON_KIND_FRAME_CALLBACKS.update(|callbacks| callbacks.retain(|cb| !range.contains(&(*cb as *const () as usize))));
+    FIGHTER_KIND_FRAME_MAIN_CALLBACKS.update(|callbacks| callbacks.retain(|cb| !range.contains(&(*cb as *const () as usize))));
+    WEAPON_KIND_FRAME_MAIN_CALLBACKS.update(|callbacks| callbacks.retain(|cb| !range.contains(&(*cb as *const () as usize))));
+}
+
+pub fn remove_item_callbacks(range: (usize, usize)) {
+    let range = range.0..range.1;
+    ITEM_FRAME_MAIN_CALLBACKS.update(|callbacks| callbacks.retain(|callback| !range.contains(&(callback.callback as *const () as usize))));
+    ITEM_KIND_FRAME_MAIN_CALLBACKS.update(|callbacks| callbacks.retain(|cb| !range.contains(&(*cb as *const () as usize))));
+    ITEM_INIT_CALLBACKS.update(|callbacks| callbacks.retain(|callback| !range.contains(&(callback.callback as *const () as usize))));
+    ITEM_KIND_INIT_CALLBACKS.update(|callbacks| callbacks.retain(|cb| !range.contains(&(*cb as *const () as usize))));
+    ITEM_RESET_CALLBACKS.update(|callbacks| callbacks.retain(|callback| !range.contains(&(callback.callback as *const () as usize))));
+    ITEM_KIND_RESET_CALLBACKS.update(|callbacks| callbacks.retain(|cb| !range.contains(&(*cb as *const () as usize))));
 }
 
 pub fn remove_fighter_init_callbacks(range: (usize, usize)) {
@@ -314,50 +827,110 @@ pub fn remove_agent_init_callbacks(range: (usize, usize)) {
 
 #[no_mangle]
 pub extern "Rust" fn replace_fighter_frame(agent: LuaConstant, original: Option<&'static mut *const extern "C" fn()>, replacement: FighterFrame) {
+    replace_fighter_frame_with_priority(agent, 0, original, replacement);
+}
+
+pub fn try_replace_fighter_frame(agent: LuaConstant, priority: i32, original: Option<&'static mut *const extern "C" fn()>, replacement: FighterFrame) -> Result<(), RegistrationError> {
+    if !crate::abi::accept_registration(replacement as *const () as usize) {
+        return Err(RegistrationError::Refused);
+    }
     let info = FighterFrameInfo {
         agent,
+        priority,
         original,
         frame: replacement
     };
-    let mut fighter_frames = FIGHTER_FRAMES.lock();
-    fighter_frames.push(info);
+    insert_by_priority(&mut FIGHTER_FRAMES.lock(), info, |info| info.priority);
+    relink_fighter_frames();
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "Rust" fn replace_fighter_frame_with_priority(agent: LuaConstant, priority: i32, original: Option<&'static mut *const extern "C" fn()>, replacement: FighterFrame) {
+    let _ = try_replace_fighter_frame(agent, priority, original, replacement);
 }
 
 #[no_mangle]
 pub extern "Rust" fn replace_weapon_frame(agent: LuaConstant, original: Option<&'static mut *const extern "C" fn()>, replacement: AgentFrame) {
+    replace_weapon_frame_with_priority(agent, 0, original, replacement);
+}
+
+pub fn try_replace_weapon_frame(agent: LuaConstant, priority: i32, original: Option<&'static mut *const extern "C" fn()>, replacement: AgentFrame) -> Result<(), RegistrationError> {
+    if !crate::abi::accept_registration(replacement as *const () as usize) {
+        return Err(RegistrationError::Refused);
+    }
     let info = AgentFrameInfo {
         agent,
+        priority,
         original,
         frame: replacement
     };
-    let mut weapon_frames = WEAPON_FRAMES.lock();
-    weapon_frames.push(info);
+    insert_by_priority(&mut WEAPON_FRAMES.lock(), info, |info| info.priority);
+    relink_weapon_frames();
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "Rust" fn replace_weapon_frame_with_priority(agent: LuaConstant, priority: i32, original: Option<&'static mut *const extern "C" fn()>, replacement: AgentFrame) {
+    let _ = try_replace_weapon_frame(agent, priority, original, replacement);
 }
 
 #[no_mangle]
 pub extern "Rust" fn replace_agent_frame_main(agent: LuaConstant, is_fighter: bool, original: Option<&'static mut *const extern "C" fn()>, replacement: AgentFrame) {
+    replace_agent_frame_main_with_priority(agent, is_fighter, 0, original, replacement);
+}
+
+pub fn try_replace_agent_frame_main(agent: LuaConstant, is_fighter: bool, priority: i32, original: Option<&'static mut *const extern "C" fn()>, replacement: AgentFrame) -> Result<(), RegistrationError> {
+    if !crate::abi::accept_registration(replacement as *const () as usize) {
+        return Err(RegistrationError::Refused);
+    }
     let info = AgentFrameMainInfo {
         agent,
         is_fighter,
+        priority,
         original,
         frame: replacement
     };
-    let mut agent_frames_main = AGENT_FRAMES_MAIN.lock();
-    agent_frames_main.push(info);
+    insert_by_priority(&mut AGENT_FRAMES_MAIN.lock(), info, |info| info.priority);
+    relink_agent_frames_main();
+    Ok(())
 }
 
 #[no_mangle]
-pub extern "Rust" fn add_fighter_reset_callback(callback: FighterReset) {
-    FIGHTER_RESETS.lock().push(callback);
+pub extern "Rust" fn replace_agent_frame_main_with_priority(agent: LuaConstant, is_fighter: bool, priority: i32, original: Option<&'static mut *const extern "C" fn()>, replacement: AgentFrame) {
+    let _ = try_replace_agent_frame_main(agent, is_fighter, priority, original, replacement);
 }
 
+// Removes a single layer, the layers around it are relinked to each other
 #[no_mangle]
-pub extern "Rust" fn add_agent_reset_callback(callback: AgentReset) {
-    AGENT_RESETS.lock().push(callback);
+pub extern "Rust" fn unregister_fighter_frame(replacement: FighterFrame) {
+    FIGHTER_FRAMES.lock().retain(|info| info.frame as *const () != replacement as *const ());
+    relink_fighter_frames();
 }
 
 #[no_mangle]
-pub extern "Rust" fn add_fighter_frame_callback(callback: FighterFrameCallback) {
+pub extern "Rust" fn unregister_weapon_frame(replacement: AgentFrame) {
+    WEAPON_FRAMES.lock().retain(|info| info.frame as *const () != replacement as *const ());
+    relink_weapon_frames();
+}
+
+#[no_mangle]
+pub extern "Rust" fn unregister_agent_frame_main(replacement: AgentFrame) {
+    AGENT_FRAMES_MAIN.lock().retain(|info| info.frame as *const () != replacement as *const ());
+    relink_agent_frames_main();
+}
+
+pub fn remove_frame_replacements(range: (usize, usize)) {
+    let range = range.0..range.1;
+    FIGHTER_FRAMES.lock().retain(|info| !range.contains(&(info.frame as *const () as usize)));
+    WEAPON_FRAMES.lock().retain(|info| !range.contains(&(info.frame as *const () as usize)));
+    AGENT_FRAMES_MAIN.lock().retain(|info| !range.contains(&(info.frame as *const () as usize)));
+    relink_fighter_frames();
+    relink_weapon_frames();
+    relink_agent_frames_main();
+}
+
+fn request_fighter_frame_hook() {
     static SHOULD_INSTALL: std::sync::Once = std::sync::Once::new();
     SHOULD_INSTALL.call_once(|| {
         unsafe {
@@ -367,11 +940,9 @@ pub extern "Rust" fn add_fighter_frame_callback(callback: FighterFrameCallback)
             }
         }
     });
-    FIGHTER_FRAME_CALLBACKS.lock().push(callback);
 }
 
-#[no_mangle]
-pub extern "Rust" fn add_weapon_frame_callback(callback: AgentFrameCallback) {
+fn request_weapon_frame_hook() {
     static SHOULD_INSTALL: std::sync::Once = std::sync::Once::new();
     SHOULD_INSTALL.call_once(|| {
         unsafe {
@@ -381,11 +952,9 @@ pub extern "Rust" fn add_weapon_frame_callback(callback: AgentFrameCallback) {
             }
         }
     });
-    WEAPON_FRAME_CALLBACKS.lock().push(callback);
 }
 
-#[no_mangle]
-pub extern "Rust" fn add_agent_frame_main_callback(callback: AgentFrameCallback) {
+fn request_agent_frame_main_hook() {
     static SHOULD_INSTALL: std::sync::Once = std::sync::Once::new();
     SHOULD_INSTALL.call_once(|| {
         unsafe {
@@ -395,20 +964,315 @@ pub extern "Rust" fn add_agent_frame_main_callback(callback: AgentFrameCallback)
             }
         }
     });
-    AGENT_FRAME_MAIN_CALLBACKS.lock().push(callback);
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_fighter_reset_callback(callback: FighterReset) {
+    if !crate::abi::accept_registration(callback as *const () as usize) {
+        return;
+    }
+    FIGHTER_RESETS.lock().push(callback);
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_agent_reset_callback(callback: AgentReset) {
+    if !crate::abi::accept_registration(callback as *const () as usize) {
+        return;
+    }
+    AGENT_RESETS.lock().push(callback);
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_fighter_frame_callback(callback: FighterFrameCallback) {
+    add_fighter_frame_callback_ordered(callback, FramePlacement::AfterOriginal, 0);
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_fighter_frame_callback_ordered(callback: FighterFrameCallback, placement: FramePlacement, order: i32) {
+    if !crate::abi::accept_registration(callback as *const () as usize) {
+        return;
+    }
+    request_fighter_frame_hook();
+    FIGHTER_FRAME_CALLBACKS.update(|callbacks| insert_ordered(callbacks, OrderedCallback { callback, placement, order }));
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_weapon_frame_callback(callback: AgentFrameCallback) {
+    add_weapon_frame_callback_ordered(callback, FramePlacement::AfterOriginal, 0);
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_weapon_frame_callback_ordered(callback: AgentFrameCallback, placement: FramePlacement, order: i32) {
+    if !crate::abi::accept_registration(callback as *const () as usize) {
+        return;
+    }
+    request_weapon_frame_hook();
+    WEAPON_FRAME_CALLBACKS.update(|callbacks| insert_ordered(callbacks, OrderedCallback { callback, placement, order }));
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_agent_frame_main_callback(callback: AgentFrameCallback) {
+    add_agent_frame_main_callback_ordered(callback, FramePlacement::AfterOriginal, 0);
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_agent_frame_main_callback_ordered(callback: AgentFrameCallback, placement: FramePlacement, order: i32) {
+    if !crate::abi::accept_registration(callback as *const () as usize) {
+        return;
+    }
+    request_agent_frame_main_hook();
+    AGENT_FRAME_MAIN_CALLBACKS.update(|callbacks| insert_ordered(callbacks, OrderedCallback { callback, placement, order }));
+}
+
+fn add_kind_callback<T: Copy>(callbacks: &Rcu<KindCallbacks<T>>, kinds: &[LuaConstant], callback: T, placement: FramePlacement, order: i32) {
+    callbacks.update(|callbacks| {
+        callbacks.add(kinds.to_vec(), OrderedCallback { callback, placement, order });
+        unsafe {
+            if crate::COMMON_MEMORY_INFO.is_some() && !callbacks.is_resolved {
+                callbacks.resolve();
+            }
+        }
+    });
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_fighter_kinds_frame_callback(kinds: &[LuaConstant], callback: FighterFrameCallback) {
+    add_fighter_kinds_frame_callback_ordered(kinds, callback, FramePlacement::AfterOriginal, 0);
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_fighter_kinds_frame_callback_ordered(kinds: &[LuaConstant], callback: FighterFrameCallback, placement: FramePlacement, order: i32) {
+    if !crate::abi::accept_registration(callback as *const () as usize) {
+        return;
+    }
+    request_fighter_frame_hook();
+    add_kind_callback(&FIGHTER_KIND_FRAME_CALLBACKS, kinds, callback, placement, order);
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_fighter_kind_frame_callback(kind: LuaConstant, callback: FighterFrameCallback) {
+    add_fighter_kinds_frame_callback(&[kind], callback);
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_weapon_kinds_frame_callback(kinds: &[LuaConstant], callback: AgentFrameCallback) {
+    add_weapon_kinds_frame_callback_ordered(kinds, callback, FramePlacement::AfterOriginal, 0);
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_weapon_kinds_frame_callback_ordered(kinds: &[LuaConstant], callback: AgentFrameCallback, placement: FramePlacement, order: i32) {
+    if !crate::abi::accept_registration(callback as *const () as usize) {
+        return;
+    }
+    request_weapon_frame_hook();
+    add_kind_callback(&WEAPON_KIND_FRAME_CALLBACKS, kinds, callback, placement, order);
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_weapon_kind_frame_callback(kind: LuaConstant, callback: AgentFrameCallback) {
+    add_weapon_kinds_frame_callback(&[kind], callback);
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_agent_kinds_frame_main_callback(kinds: &[LuaConstant], is_fighter: bool, callback: AgentFrameCallback) {
+    add_agent_kinds_frame_main_callback_ordered(kinds, is_fighter, callback, FramePlacement::AfterOriginal, 0);
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_agent_kinds_frame_main_callback_ordered(kinds: &[LuaConstant], is_fighter: bool, callback: AgentFrameCallback, placement: FramePlacement, order: i32) {
+    if !crate::abi::accept_registration(callback as *const () as usize) {
+        return;
+    }
+    request_agent_frame_main_hook();
+    if is_fighter {
+        add_kind_callback(&FIGHTER_KIND_FRAME_MAIN_CALLBACKS, kinds, callback, placement, order);
+    } else {
+        add_kind_callback(&WEAPON_KIND_FRAME_MAIN_CALLBACKS, kinds, callback, placement, order);
+    }
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_agent_kind_frame_main_callback(kind: LuaConstant, is_fighter: bool, callback: AgentFrameCallback) {
+    add_agent_kinds_frame_main_callback(&[kind], is_fighter, callback);
+}
+
+// Any of the filters can be left out to match everything. The callback gets the status being left and the one being entered
+#[no_mangle]
+pub extern "Rust" fn add_status_change_callback(kind: Option<LuaConstant>, is_fighter: bool, from: Option<LuaConstant>, to: Option<LuaConstant>, callback: StatusChangeCallback) {
+    if !crate::abi::accept_registration(callback as *const () as usize) {
+        return;
+    }
+    let info = StatusChangeInfo {
+        kind,
+        is_fighter,
+        from,
+        to,
+        callback
+    };
+    let is_first = STATUS_CHANGE_CALLBACKS.update(|callbacks| {
+        let is_first = callbacks.registrations.is_empty();
+        callbacks.add(info);
+        unsafe {
+            if crate::COMMON_MEMORY_INFO.is_some() && !callbacks.is_resolved {
+                callbacks.resolve();
+            }
+        }
+        is_first
+    });
+    // agents created after this get hooked as their status scripts are set
+    if is_first {
+        for agent in crate::scripts::get_live_status_agents() {
+            unsafe {
+                hook_status_changes(agent);
+            }
+        }
+    }
 }
 
 #[no_mangle]
 pub extern "Rust" fn add_fighter_init_callback(callback: FighterInit) {
+    if !crate::abi::accept_registration(callback as *const () as usize) {
+        return;
+    }
     FIGHTER_INIT_CALLBACKS.lock().push(callback);
 }
 
 #[no_mangle]
 pub extern "Rust" fn add_agent_init_callback(callback: AgentInit) {
+    if !crate::abi::accept_registration(callback as *const () as usize) {
+        return;
+    }
     AGENT_INIT_CALLBACKS.lock().push(callback);
 }
 
+#[no_mangle]
+pub extern "Rust" fn add_item_frame_main_callback(callback: ItemFrameCallback) {
+    add_item_frame_main_callback_ordered(callback, FramePlacement::AfterOriginal, 0);
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_item_frame_main_callback_ordered(callback: ItemFrameCallback, placement: FramePlacement, order: i32) {
+    if !crate::abi::accept_registration(callback as *const () as usize) {
+        return;
+    }
+    request_item_hooks();
+    ITEM_FRAME_MAIN_CALLBACKS.update(|callbacks| insert_ordered(callbacks, OrderedCallback { callback, placement, order }));
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_item_kinds_frame_main_callback(kinds: &[LuaConstant], callback: ItemFrameCallback) {
+    add_item_kinds_frame_main_callback_ordered(kinds, callback, FramePlacement::AfterOriginal, 0);
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_item_kinds_frame_main_callback_ordered(kinds: &[LuaConstant], callback: ItemFrameCallback, placement: FramePlacement, order: i32) {
+    if !crate::abi::accept_registration(callback as *const () as usize) {
+        return;
+    }
+    request_item_hooks();
+    add_kind_callback(&ITEM_KIND_FRAME_MAIN_CALLBACKS, kinds, callback, placement, order);
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_item_kind_frame_main_callback(kind: LuaConstant, callback: ItemFrameCallback) {
+    add_item_kinds_frame_main_callback(&[kind], callback);
+}
+
+// Init and reset callbacks run before the original unless placed otherwise
+#[no_mangle]
+pub extern "Rust" fn add_item_init_callback(callback: ItemInit) {
+    add_item_init_callback_ordered(callback, FramePlacement::BeforeOriginal, 0);
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_item_init_callback_ordered(callback: ItemInit, placement: FramePlacement, order: i32) {
+    if !crate::abi::accept_registration(callback as *const () as usize) {
+        return;
+    }
+    request_item_hooks();
+    ITEM_INIT_CALLBACKS.update(|callbacks| insert_ordered(callbacks, OrderedCallback { callback, placement, order }));
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_item_kinds_init_callback(kinds: &[LuaConstant], callback: ItemInit) {
+    add_item_kinds_init_callback_ordered(kinds, callback, FramePlacement::BeforeOriginal, 0);
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_item_kinds_init_callback_ordered(kinds: &[LuaConstant], callback: ItemInit, placement: FramePlacement, order: i32) {
+    if !crate::abi::accept_registration(callback as *const () as usize) {
+        return;
+    }
+    request_item_hooks();
+    add_kind_callback(&ITEM_KIND_INIT_CALLBACKS, kinds, callback, placement, order);
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_item_reset_callback(callback: ItemReset) {
+    add_item_reset_callback_ordered(callback, FramePlacement::BeforeOriginal, 0);
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_item_reset_callback_ordered(callback: ItemReset, placement: FramePlacement, order: i32) {
+    if !crate::abi::accept_registration(callback as *const () as usize) {
+        return;
+    }
+    request_item_hooks();
+    ITEM_RESET_CALLBACKS.update(|callbacks| insert_ordered(callbacks, OrderedCallback { callback, placement, order }));
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_item_kinds_reset_callback(kinds: &[LuaConstant], callback: ItemReset) {
+    add_item_kinds_reset_callback_ordered(kinds, callback, FramePlacement::BeforeOriginal, 0);
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_item_kinds_reset_callback_ordered(kinds: &[LuaConstant], callback: ItemReset, placement: FramePlacement, order: i32) {
+    if !crate::abi::accept_registration(callback as *const () as usize) {
+        return;
+    }
+    request_item_hooks();
+    add_kind_callback(&ITEM_KIND_RESET_CALLBACKS, kinds, callback, placement, order);
+}
+
+// The item system lines live in the item module rather than common, so they are hooked whenever it loads, and
+// only once something registers an item callback. A registration made while it is loaded hooks it right away
+fn request_item_hooks() {
+    if !SHOULD_INSTALL_ITEM_CB.swap(true, Ordering::SeqCst) {
+        install_item_hooks();
+    }
+}
+
+fn install_item_hooks() {
+    let mut item_module = ITEM_MODULE.lock();
+    if let Some((module_object, hooked)) = item_module.as_mut() {
+        if *hooked || !SHOULD_INSTALL_ITEM_CB.load(Ordering::SeqCst) {
+            return;
+        }
+        let module_object = *module_object as *mut ModuleObject;
+        unsafe {
+            crate::hooks::inline_symbol_hook(module_object, "_ZN7lua2cpp11L2CItemBase30sys_line_status_system_controlEv", item_frame_main_callbacks as *const extern "C" fn(), &mut ITEM_FRAME_MAIN_ORIGINAL);
+            crate::hooks::inline_symbol_hook(module_object, "_ZN7lua2cpp11L2CItemBase27sys_line_status_system_initEv", item_init_callbacks as *const extern "C" fn(), &mut ITEM_INIT_ORIGINAL);
+            crate::hooks::inline_symbol_hook(module_object, "_ZN7lua2cpp11L2CItemBase5RESETEv", item_reset_callbacks as *const extern "C" fn(), &mut ITEM_RESET_ORIGINAL);
+        }
+        *hooked = true;
+    }
+}
+
 fn install() {
+    relink_fighter_frames();
+    relink_weapon_frames();
+    relink_agent_frames_main();
+
+    FIGHTER_KIND_FRAME_CALLBACKS.update(|callbacks| callbacks.resolve());
+    WEAPON_KIND_FRAME_CALLBACKS.update(|callbacks| callbacks.resolve());
+    FIGHTER_KIND_FRAME_MAIN_CALLBACKS.update(|callbacks| callbacks.resolve());
+    WEAPON_KIND_FRAME_MAIN_CALLBACKS.update(|callbacks| callbacks.resolve());
+    STATUS_CHANGE_CALLBACKS.update(|callbacks| callbacks.resolve());
+    ITEM_KIND_FRAME_MAIN_CALLBACKS.update(|callbacks| callbacks.resolve());
+    ITEM_KIND_INIT_CALLBACKS.update(|callbacks| callbacks.resolve());
+    ITEM_KIND_RESET_CALLBACKS.update(|callbacks| callbacks.resolve());
+
     skyline::install_hooks!(
         sys_line_system_fighter_init_replace,
         sys_line_system_init_replace,
@@ -433,9 +1297,21 @@ fn install() {
 pub fn nro_load(info: &NroInfo) {
     if info.name == "common" {
         install();
+    } else if info.name == "item" {
+        *ITEM_MODULE.lock() = Some((info.module.ModuleObject as usize, false));
+        install_item_hooks();
     }
 }
 
-pub fn nro_unload(_: &NroInfo) {
-
+pub fn nro_unload(info: &NroInfo) {
+    if info.name == "item" {
+        // the hooks went away with the module's code
+        let mut item_module = ITEM_MODULE.lock();
+        *item_module = None;
+        unsafe {
+            ITEM_FRAME_MAIN_ORIGINAL = 0 as _;
+            ITEM_INIT_ORIGINAL = 0 as _;
+            ITEM_RESET_ORIGINAL = 0 as _;
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,237 @@
+use std::ffi::{c_void, CStr};
+use std::os::raw::c_char;
+
+use smash::lua2cpp::{L2CFighterCommon, L2CFighterBase};
+use smash::lib::L2CValue;
+use smash::phx::Hash40;
+
+use crate::abi::RegistrationError;
+use crate::acmd::{AgentTarget, Category};
+use crate::LuaConstant;
+
+// C ABI mirror of the Rust exports, for plugins that aren't written in Rust.
+// include/smashline_hook.h is generated from this file with cbindgen, regenerate it after changing anything here.
+// This is intentionally only the replacement half of the API: the callback registrations (frame, init, reset,
+// status change, agent events, items) take Rust fn pointers and Rust types, and storage, the catalogue,
+// validation and labels hand back Rust collections, so those stay Rust only.
+
+pub const SMASHLINE_RESULT_OK: i32 = 0;
+pub const SMASHLINE_RESULT_NULL_ARGUMENT: i32 = -1;
+pub const SMASHLINE_RESULT_INVALID_STRING: i32 = -2;
+pub const SMASHLINE_RESULT_INVALID_CATEGORY: i32 = -3;
+pub const SMASHLINE_RESULT_REFUSED: i32 = -4;
+pub const SMASHLINE_RESULT_CONFLICT: i32 = -5;
+
+pub const SMASHLINE_ACMD_GAME: u32 = 0;
+pub const SMASHLINE_ACMD_EFFECT: u32 = 1;
+pub const SMASHLINE_ACMD_SOUND: u32 = 2;
+pub const SMASHLINE_ACMD_EXPRESSION: u32 = 3;
+
+#[repr(C)]
+pub struct SmashlineAcmdScript {
+    pub agent: u64,
+    pub script: u64,
+    pub category: u32,
+    pub low_priority: bool,
+    pub replacement: *const c_void,
+    pub original: *mut *const c_void
+}
+
+#[repr(C)]
+pub struct SmashlineStatusScript {
+    pub agent: u64,
+    pub status: i32,
+    pub condition: i32,
+    pub low_priority: bool,
+    pub replacement: *const c_void,
+    pub original: *mut *const c_void
+}
+
+#[repr(C)]
+pub struct SmashlineCommonStatusScript {
+    pub status: i32,
+    pub condition: i32,
+    pub replacement: *const c_void,
+    pub original: *mut *const c_void
+}
+
+#[repr(C)]
+pub struct SmashlineMoveCustomizer {
+    pub agent: u64,
+    pub low_priority: bool,
+    pub replacement: *const c_void,
+    pub original: *mut *const c_void
+}
+
+#[repr(C)]
+pub struct SmashlineSymbolReplacement {
+    pub module: *const c_char,
+    pub symbol: *const c_char,
+    pub replacement: *const c_void,
+    pub original: *mut *const c_void
+}
+
+#[repr(C)]
+pub struct SmashlineFrameReplacement {
+    pub kind: i32,
+    pub is_fighter: bool,
+    pub replacement: *const c_void,
+    pub original: *mut *const c_void
+}
+
+unsafe fn get_original(original: *mut *const c_void) -> Option<&'static mut *const extern "C" fn()> {
+    if original.is_null() {
+        None
+    } else {
+        Some(&mut *(original as *mut *const extern "C" fn()))
+    }
+}
+
+unsafe fn get_str(string: *const c_char) -> Result<&'static str, i32> {
+    if string.is_null() {
+        return Err(SMASHLINE_RESULT_NULL_ARGUMENT);
+    }
+    CStr::from_ptr(string).to_str().map_err(|_| SMASHLINE_RESULT_INVALID_STRING)
+}
+
+fn get_result(result: Result<(), RegistrationError>) -> i32 {
+    match result {
+        Ok(()) => SMASHLINE_RESULT_OK,
+        Err(RegistrationError::Refused) => SMASHLINE_RESULT_REFUSED,
+        Err(RegistrationError::Conflict) => SMASHLINE_RESULT_CONFLICT
+    }
+}
+
+fn get_category(category: u32) -> Option<Category> {
+    match category {
+        SMASHLINE_ACMD_GAME => Some(Category::ACMD_GAME),
+        SMASHLINE_ACMD_EFFECT => Some(Category::ACMD_EFFECT),
+        SMASHLINE_ACMD_SOUND => Some(Category::ACMD_SOUND),
+        SMASHLINE_ACMD_EXPRESSION => Some(Category::ACMD_EXPRESSION),
+        _ => None
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn smashline_register_plugin(address: *const c_void) -> i32 {
+    if address.is_null() {
+        return SMASHLINE_RESULT_NULL_ARGUMENT;
+    }
+    if crate::abi::accept_registration(address as usize) {
+        SMASHLINE_RESULT_OK
+    } else {
+        SMASHLINE_RESULT_REFUSED
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn smashline_replace_acmd_script(script: *const SmashlineAcmdScript) -> i32 {
+    let script = match script.as_ref() {
+        Some(script) if !script.replacement.is_null() => script,
+        _ => return SMASHLINE_RESULT_NULL_ARGUMENT
+    };
+    let category = match get_category(script.category) {
+        Some(category) => category,
+        None => return SMASHLINE_RESULT_INVALID_CATEGORY
+    };
+    get_result(crate::acmd::try_replace_acmd_script(
+        Hash40::new_raw(script.agent),
+        Hash40::new_raw(script.script),
+        get_original(script.original),
+        category,
+        script.low_priority,
+        AgentTarget::All,
+        script.replacement as *const extern "C-unwind" fn()
+    ))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn smashline_replace_status_script(script: *const SmashlineStatusScript) -> i32 {
+    let script = match script.as_ref() {
+        Some(script) if !script.replacement.is_null() => script,
+        _ => return SMASHLINE_RESULT_NULL_ARGUMENT
+    };
+    get_result(crate::status::try_replace_status_script(
+        Hash40::new_raw(script.agent),
+        LuaConstant::Evaluated(script.status),
+        LuaConstant::Evaluated(script.condition),
+        get_original(script.original),
+        script.low_priority,
+        script.replacement as *const extern "C-unwind" fn()
+    ))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn smashline_replace_common_status_script(script: *const SmashlineCommonStatusScript) -> i32 {
+    let script = match script.as_ref() {
+        Some(script) if !script.replacement.is_null() => script,
+        _ => return SMASHLINE_RESULT_NULL_ARGUMENT
+    };
+    get_result(crate::status::try_replace_common_status_script(
+        LuaConstant::Evaluated(script.status),
+        LuaConstant::Evaluated(script.condition),
+        get_original(script.original),
+        script.replacement as *const extern "C-unwind" fn()
+    ))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn smashline_replace_move_customizer(customizer: *const SmashlineMoveCustomizer) -> i32 {
+    let customizer = match customizer.as_ref() {
+        Some(customizer) if !customizer.replacement.is_null() => customizer,
+        _ => return SMASHLINE_RESULT_NULL_ARGUMENT
+    };
+    get_result(crate::status::try_replace_move_customizer(
+        Hash40::new_raw(customizer.agent),
+        get_original(customizer.original),
+        customizer.low_priority,
+        customizer.replacement as *const extern "C" fn()
+    ))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn smashline_replace_symbol(replacement: *const SmashlineSymbolReplacement) -> i32 {
+    let replacement = match replacement.as_ref() {
+        Some(replacement) if !replacement.replacement.is_null() => replacement,
+        _ => return SMASHLINE_RESULT_NULL_ARGUMENT
+    };
+    let module = match get_str(replacement.module) {
+        Ok(module) => module,
+        Err(result) => return result
+    };
+    let symbol = match get_str(replacement.symbol) {
+        Ok(symbol) => symbol,
+        Err(result) => return result
+    };
+    get_result(crate::hooks::try_replace_symbol(module, symbol, replacement.replacement as *const extern "C" fn(), get_original(replacement.original)))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn smashline_replace_fighter_frame(replacement: *const SmashlineFrameReplacement) -> i32 {
+    let replacement = match replacement.as_ref() {
+        Some(replacement) if !replacement.replacement.is_null() => replacement,
+        _ => return SMASHLINE_RESULT_NULL_ARGUMENT
+    };
+    let frame: extern "C" fn(&mut L2CFighterCommon) -> L2CValue = std::mem::transmute(replacement.replacement);
+    get_result(crate::callbacks::try_replace_fighter_frame(LuaConstant::Evaluated(replacement.kind), 0, get_original(replacement.original), frame))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn smashline_replace_weapon_frame(replacement: *const SmashlineFrameReplacement) -> i32 {
+    let replacement = match replacement.as_ref() {
+        Some(replacement) if !replacement.replacement.is_null() => replacement,
+        _ => return SMASHLINE_RESULT_NULL_ARGUMENT
+    };
+    let frame: extern "C" fn(&mut L2CFighterBase) -> L2CValue = std::mem::transmute(replacement.replacement);
+    get_result(crate::callbacks::try_replace_weapon_frame(LuaConstant::Evaluated(replacement.kind), 0, get_original(replacement.original), frame))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn smashline_replace_agent_frame_main(replacement: *const SmashlineFrameReplacement) -> i32 {
+    let replacement = match replacement.as_ref() {
+        Some(replacement) if !replacement.replacement.is_null() => replacement,
+        _ => return SMASHLINE_RESULT_NULL_ARGUMENT
+    };
+    let frame: extern "C" fn(&mut L2CFighterBase) -> L2CValue = std::mem::transmute(replacement.replacement);
+    get_result(crate::callbacks::try_replace_agent_frame_main(LuaConstant::Evaluated(replacement.kind), replacement.is_fighter, 0, get_original(replacement.original), frame))
+}
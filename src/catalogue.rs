@@ -0,0 +1,112 @@
+use smash::lua2cpp::L2CAgentBase;
+use smash::lib::{L2CValue, lua_const::{LUA_SCRIPT_STATUS_FUNC_STATUS_PRE, LUA_SCRIPT_STATUS_FUNC_STATUS_MAIN}};
+use smash::phx::Hash40;
+
+use crate::acmd::Category;
+
+// Reads what scripts an agent actually defines from a live instance of it, so nothing can be listed
+// until that agent has been created at least once during the session.
+
+// status kinds and conditions are small indices, nothing in the game comes close to these
+const MAX_STATUS_KIND: i32 = 0x400;
+const MAX_STATUS_CONDITION: i32 = 0x20;
+
+// libc++ std::unordered_map<Hash40, void*>, which is what L2CAgentBase::functions is
+#[repr(C)]
+struct FunctionMapNode {
+    pub next: *const FunctionMapNode,
+    pub hash: u64,
+    pub key: u64,
+    pub function: u64
+}
+
+#[repr(C)]
+struct FunctionMap {
+    pub buckets: u64,
+    pub bucket_count: u64,
+    pub first: *const FunctionMapNode,
+    pub size: u64
+}
+
+// both are mirrored from libc++ by hand, make sure they at least still have the sizes it gives them
+const _: () = assert!(std::mem::size_of::<FunctionMapNode>() == 0x20);
+const _: () = assert!(std::mem::size_of::<FunctionMap>() == 0x20);
+
+pub unsafe fn read_function_hashes(agent: *mut L2CAgentBase) -> Vec<Hash40> {
+    assert_eq!(std::mem::size_of_val(&(*agent).functions), std::mem::size_of::<FunctionMap>(), "L2CAgentBase::functions is not the map we expect");
+    let map = &*(&(*agent).functions as *const _ as *const FunctionMap);
+    let mut hashes = Vec::with_capacity(map.size as usize);
+    let mut node = map.first;
+    // never trust a linked list from game memory to end
+    while !node.is_null() && (hashes.len() as u64) < map.size {
+        hashes.push(Hash40::new_raw((*node).key));
+        node = (*node).next;
+    }
+    hashes
+}
+
+// Status kinds are numbered in one mostly contiguous run per agent, once this many in a row come up empty
+// there is nothing further to find
+const MAX_EMPTY_STATUS_RUN: i32 = 0x40;
+
+// Every status kind the agent has a function for under the condition
+pub unsafe fn read_status_kinds(agent: *mut L2CAgentBase, condition: i32) -> Vec<i32> {
+    let mut kinds = Vec::new();
+    let mut empty_run = 0;
+    for status in 0..MAX_STATUS_KIND {
+        let func = (*agent).sv_get_status_func(&L2CValue::I32(status), &L2CValue::I32(condition)).get_ptr();
+        if func.is_null() {
+            empty_run += 1;
+            if empty_run >= MAX_EMPTY_STATUS_RUN {
+                break;
+            }
+        } else {
+            empty_run = 0;
+            kinds.push(status);
+        }
+    }
+    kinds
+}
+
+// Every status has a pre function and nearly every one a main, so those are enough to find the agent's status
+// kinds, and only those need their other conditions checked
+unsafe fn read_status_functions(agent: *mut L2CAgentBase) -> Vec<(i32, i32)> {
+    let mut kinds = read_status_kinds(agent, *LUA_SCRIPT_STATUS_FUNC_STATUS_PRE);
+    kinds.extend(read_status_kinds(agent, *LUA_SCRIPT_STATUS_FUNC_STATUS_MAIN));
+    kinds.sort_unstable();
+    kinds.dedup();
+    let mut statuses = Vec::new();
+    for status in kinds {
+        for condition in 0..MAX_STATUS_CONDITION {
+            let func = (*agent).sv_get_status_func(&L2CValue::I32(status), &L2CValue::I32(condition)).get_ptr();
+            if !func.is_null() {
+                statuses.push((status, condition));
+            }
+        }
+    }
+    statuses
+}
+
+// Every ACMD script hash the agent defines for the category, or None if the agent hasn't been created yet
+#[no_mangle]
+pub extern "Rust" fn get_agent_acmd_scripts(agent: Hash40, category: Category) -> Option<Vec<Hash40>> {
+    crate::scripts::get_live_acmd_agent(agent, category).map(|agent| unsafe { read_function_hashes(agent) })
+}
+
+// Every (status kind, condition) pair the agent has a status function for, or None if the agent hasn't been created yet
+#[no_mangle]
+pub extern "Rust" fn get_agent_status_scripts(agent: Hash40) -> Option<Vec<(i32, i32)>> {
+    crate::scripts::get_live_status_agent(agent).map(|agent| unsafe { read_status_functions(agent) })
+}
+
+#[no_mangle]
+pub extern "Rust" fn agent_has_acmd_script(agent: Hash40, category: Category, script: Hash40) -> Option<bool> {
+    get_agent_acmd_scripts(agent, category).map(|scripts| scripts.iter().any(|hash| hash.hash == script.hash))
+}
+
+#[no_mangle]
+pub extern "Rust" fn agent_has_status_script(agent: Hash40, status: i32, condition: i32) -> Option<bool> {
+    crate::scripts::get_live_status_agent(agent).map(|agent| unsafe {
+        !(*agent).sv_get_status_func(&L2CValue::I32(status), &L2CValue::I32(condition)).get_ptr().is_null()
+    })
+}
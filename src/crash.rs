@@ -0,0 +1,200 @@
+use std::arch::asm;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use parking_lot::Mutex;
+
+use skyline::nro::NroInfo;
+use nnsdk::root::rtld::ModuleObject;
+
+use crate::nx::{svc, MemoryPermission};
+use crate::rtld;
+
+const CRASH_REPORT_DIRECTORY: &str = "sd:/atmosphere/contents/01006A800016E000/romfs/smashline/crash_reports";
+const MAX_BACKTRACE_DEPTH: usize = 64;
+
+struct LoadedModule {
+    pub name: String,
+    pub module_object: usize,
+    pub begin: usize,
+    pub end: usize,
+    // built the first time a crash lands in this module
+    pub symbols: Option<Vec<(u64, u32)>>
+}
+
+unsafe impl Send for LoadedModule {}
+
+lazy_static! {
+    static ref LOADED_MODULES: Mutex<Vec<LoadedModule>> = Mutex::new(Vec::new());
+}
+
+static IS_WRITING_REPORT: AtomicBool = AtomicBool::new(false);
+
+pub fn nro_load(info: &NroInfo) {
+    unsafe {
+        let module_object = info.module.ModuleObject as *const ModuleObject;
+        if let Some((begin, end)) = rtld::get_module_range(module_object) {
+            LOADED_MODULES.lock().push(LoadedModule {
+                name: String::from(info.name),
+                module_object: module_object as usize,
+                begin,
+                end,
+                symbols: None
+            });
+        }
+    }
+}
+
+pub fn nro_unload(info: &NroInfo) {
+    let mut modules = LOADED_MODULES.lock();
+    let mut new_modules = Vec::with_capacity(modules.len());
+    for module in modules.drain(..) {
+        if module.name != info.name {
+            new_modules.push(module);
+        }
+    }
+    *modules = new_modules;
+}
+
+pub fn is_readable(address: usize) -> bool {
+    if address == 0 || address % 8 != 0 {
+        return false;
+    }
+    match svc::query_memory(address) {
+        Ok(result) => result.mem_info.permission.contains(MemoryPermission::READ),
+        Err(_) => false
+    }
+}
+
+// Walks the frame pointer chain, since we can't rely on the unwinder when we are being called from abort
+unsafe fn capture_backtrace() -> Vec<usize> {
+    let mut frames = Vec::new();
+    let mut frame_pointer: usize;
+    asm!("mov {}, x29", out(reg) frame_pointer);
+    while frames.len() < MAX_BACKTRACE_DEPTH && is_readable(frame_pointer) {
+        let return_address = *(frame_pointer as *const usize).add(1);
+        if return_address == 0 {
+            break;
+        }
+        frames.push(return_address);
+        let next = *(frame_pointer as *const usize);
+        if next <= frame_pointer {
+            break;
+        }
+        frame_pointer = next;
+    }
+    frames
+}
+
+fn nearest_symbol(symbols: &[(u64, u32)], offset: u64) -> Option<(u64, u32)> {
+    let index = symbols.partition_point(|(value, _)| *value <= offset);
+    if index == 0 {
+        None
+    } else {
+        Some(symbols[index - 1])
+    }
+}
+
+// (module name, module object, nearest symbol) for an address inside a module we saw get loaded
+unsafe fn find_in_loaded_modules(address: usize) -> Option<(String, *const ModuleObject, Option<(String, usize)>)> {
+    let mut modules = LOADED_MODULES.try_lock()?;
+    let module = modules.iter_mut().find(|module| module.begin <= address && address < module.end)?;
+    let module_object = module.module_object as *const ModuleObject;
+    let symbols = module.symbols.get_or_insert_with(|| rtld::collect_symbols(module_object));
+    let offset = (address - module.begin) as u64;
+    let symbol = nearest_symbol(symbols, offset).map(|(value, name)| (rtld::get_symbol_name(module_object, name), (offset - value) as usize));
+    Some((module.name.clone(), module_object, symbol))
+}
+
+// (module name, module object, nearest symbol), falling back to rtld for modules loaded before we were
+unsafe fn locate(address: usize) -> Option<(String, *const ModuleObject, Option<(String, usize)>)> {
+    if let Some(found) = find_in_loaded_modules(address) {
+        return Some(found);
+    }
+    let module_object = rtld::try_get_module_object_from_address(address)?;
+    let name = format!("module@{:#x}", (*module_object).module_base);
+    Some((name, module_object as *const ModuleObject, rtld::get_nearest_symbol(module_object, address)))
+}
+
+unsafe fn format_frame(index: usize, address: usize, report: &mut String) {
+    let _ = write!(report, "{:>3}: {:#018x}", index, address);
+    // return addresses point after the call, step back so we land on the calling instruction
+    let (name, module_object, symbol) = match locate(address - 4) {
+        Some(found) => found,
+        None => {
+            let _ = writeln!(report, " (unknown module)");
+            return;
+        }
+    };
+    let module_base = (*module_object).module_base as usize;
+    let _ = write!(report, " {} + {:#x}", name, address - module_base);
+    if let Some((symbol, offset)) = symbol {
+        let _ = write!(report, " ({} + {:#x})", symbol, offset + 4);
+    }
+    if let Some((begin, _)) = crate::unwind::get_skyline_plugin_range(address) {
+        let _ = write!(report, " [skyline plugin @ {:#x}]", begin);
+    }
+    let _ = writeln!(report);
+}
+
+// module + offset (symbol) for a function pointer, for logs that would otherwise only have the raw address
+pub fn describe_address(address: usize) -> String {
+    match unsafe { locate(address) } {
+        Some((name, module_object, symbol)) => {
+            let offset = address - unsafe { (*module_object).module_base as usize };
+            match symbol {
+                Some((symbol, 0)) => format!("{} ({} + {:#x})", symbol, name, offset),
+                Some((symbol, symbol_offset)) => format!("{} + {:#x} ({} + {:#x})", symbol, symbol_offset, name, offset),
+                None => format!("{} + {:#x}", name, offset)
+            }
+        },
+        None => format!("{:#x}", address)
+    }
+}
+
+fn build_report(reason: &str) -> String {
+    let mut report = String::new();
+    let _ = writeln!(report, "smashline crash report");
+    let _ = writeln!(report, "Reason: {}", reason);
+    match crate::loader::get_development_plugin_range() {
+        Some((begin, end)) => {
+            let _ = writeln!(report, "Development plugin: {} ({:#x} - {:#x})", crate::loader::DEVELOPMENT_PLUGIN_PATH, begin, end);
+        },
+        None => {
+            let _ = writeln!(report, "Development plugin: none");
+        }
+    }
+    if let Some(script) = crate::shims::try_describe_running() {
+        let _ = writeln!(report, "Running replacement: {}", script);
+    }
+    let _ = writeln!(report, "Backtrace:");
+    unsafe {
+        for (index, address) in capture_backtrace().into_iter().enumerate() {
+            format_frame(index, address, &mut report);
+        }
+    }
+    report
+}
+
+pub fn write_crash_report(reason: &str) {
+    // abort can be reached again while we are writing the report, don't recurse
+    if IS_WRITING_REPORT.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    let report = build_report(reason);
+    println!("{}", report);
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let path = format!("{}/crash_{}.txt", CRASH_REPORT_DIRECTORY, timestamp);
+    if std::fs::create_dir_all(CRASH_REPORT_DIRECTORY).is_err() || std::fs::write(&path, report).is_err() {
+        println!("[smashline::crash] Failed to write crash report to {}", path);
+    } else {
+        println!("[smashline::crash] Crash report written to {}", path);
+    }
+
+    IS_WRITING_REPORT.store(false, Ordering::SeqCst);
+}
@@ -0,0 +1,80 @@
+use smash::lua2cpp::L2CAgentBase;
+use smash::phx::Hash40;
+use parking_lot::Mutex;
+use std::collections::HashSet;
+
+use crate::acmd::Category;
+use crate::rcu::Rcu;
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum AgentCategory {
+    Acmd(Category),
+    Status
+}
+
+#[derive(Clone, Copy)]
+pub struct AgentEvent {
+    pub agent: *mut L2CAgentBase,
+    pub hash: Hash40,
+    pub category: AgentCategory,
+    pub is_share: bool
+}
+
+type AgentEventCallback = fn(&AgentEvent);
+
+lazy_static! {
+    // fired from inside of the create_agent wrappers and vtable dtors, see scripts.rs
+    static ref AGENT_CREATE_CALLBACKS: Rcu<Vec<AgentEventCallback>> = Rcu::new(Vec::new());
+    static ref AGENT_DESTROY_CALLBACKS: Rcu<Vec<AgentEventCallback>> = Rcu::new(Vec::new());
+    // every agent a create event went out for and no destroy event has yet, so that an agent is only ever
+    // destroyed once no matter how many of its destructors end up in our vtables
+    static ref CREATED_AGENTS: Mutex<HashSet<usize>> = Mutex::new(HashSet::new());
+}
+
+fn fire(callbacks: &Rcu<Vec<AgentEventCallback>>, event: AgentEvent) {
+    let address_of = |cb: AgentEventCallback| cb as *const () as usize;
+    let mut panicked = Vec::new();
+    for callback in callbacks.read().iter() {
+        if !crate::shims::call_guarded(address_of(*callback), || callback(&event)) {
+            panicked.push(address_of(*callback));
+        }
+    }
+    if !panicked.is_empty() {
+        callbacks.update(|callbacks| callbacks.retain(|cb| !panicked.contains(&address_of(*cb))));
+    }
+}
+
+pub fn agent_created(agent: *mut L2CAgentBase, hash: Hash40, category: AgentCategory, is_share: bool) {
+    CREATED_AGENTS.lock().insert(agent as usize);
+    fire(&AGENT_CREATE_CALLBACKS, AgentEvent { agent, hash, category, is_share });
+}
+
+pub fn agent_destroyed(agent: *mut L2CAgentBase, hash: Hash40, category: AgentCategory, is_share: bool) {
+    if !CREATED_AGENTS.lock().remove(&(agent as usize)) {
+        return;
+    }
+    fire(&AGENT_DESTROY_CALLBACKS, AgentEvent { agent, hash, category, is_share });
+}
+
+pub fn remove_agent_event_callbacks(range: (usize, usize)) {
+    let range = range.0..range.1;
+    AGENT_CREATE_CALLBACKS.update(|callbacks| callbacks.retain(|cb| !range.contains(&(*cb as *const () as usize))));
+    AGENT_DESTROY_CALLBACKS.update(|callbacks| callbacks.retain(|cb| !range.contains(&(*cb as *const () as usize))));
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_agent_create_callback(callback: AgentEventCallback) {
+    if !crate::abi::accept_registration(callback as *const () as usize) {
+        return;
+    }
+    AGENT_CREATE_CALLBACKS.update(|callbacks| callbacks.push(callback));
+}
+
+// The agent pointer is still valid for the duration of the callback, the original destructor runs afterwards
+#[no_mangle]
+pub extern "Rust" fn add_agent_destroy_callback(callback: AgentEventCallback) {
+    if !crate::abi::accept_registration(callback as *const () as usize) {
+        return;
+    }
+    AGENT_DESTROY_CALLBACKS.update(|callbacks| callbacks.push(callback));
+}
@@ -0,0 +1,510 @@
+// Pulls the agent hashes out of a create_agent_fighter_* function.
+// These functions compare the requested hash against every agent the module knows about, so we emulate
+// just enough of aarch64 to know what is in each register when a comparison happens. Anything we can't
+// follow makes the result incomplete instead of wrong, and the caller treats that as an error.
+
+use aarch64_decode::Instr;
+
+// used when the symbol has no size, matches how far the old RET-terminated scan could realistically go
+pub const MAX_UNSIZED_INSTRUCTIONS: usize = 0x1000;
+
+const REGISTER_COUNT: usize = 31;
+const ZR: u32 = 31;
+
+pub struct ExtractedHashes {
+    pub hashes: Vec<u64>,
+    // false if a comparison used registers we couldn't resolve
+    pub is_complete: bool
+}
+
+// Hash40s are a crc32 in the low 32 bits and the string length in the next 8, nothing above that
+pub fn looks_like_hash40(value: u64) -> bool {
+    value >> 40 == 0 && (value >> 32) & 0xFF != 0
+}
+
+fn sign_extend(value: u64, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    ((value << shift) as i64) >> shift
+}
+
+// aarch64_decode tells us what an instruction is, the immediates are pulled out of the raw word here since
+// their encodings (scaled, split or shifted) need handling either way
+fn rd(instr: u32) -> u32 {
+    instr & 0x1F
+}
+
+fn rn(instr: u32) -> u32 {
+    (instr >> 5) & 0x1F
+}
+
+fn rm(instr: u32) -> u32 {
+    (instr >> 16) & 0x1F
+}
+
+fn rt2(instr: u32) -> u32 {
+    (instr >> 10) & 0x1F
+}
+
+fn imm9(instr: u32) -> i64 {
+    sign_extend(((instr >> 12) & 0x1FF) as u64, 9)
+}
+
+fn movewide_shift(instr: u32) -> u32 {
+    ((instr >> 21) & 0x3) * 16
+}
+
+// DecodeBitMasks from the ARM ARM, only the wmask is needed for ORR immediate
+fn decode_bit_mask(is_64: bool, n: u32, imms: u32, immr: u32) -> Option<u64> {
+    let combined = (n << 6) | (!imms & 0x3F);
+    if combined == 0 {
+        return None;
+    }
+    let len = 31 - combined.leading_zeros();
+    if len < 1 || (!is_64 && len > 5) {
+        return None;
+    }
+    let size = 1u32 << len;
+    let levels = size - 1;
+    let s = imms & levels;
+    let r = immr & levels;
+    if s == levels {
+        return None;
+    }
+    let welem = if s + 1 == 64 { u64::MAX } else { (1u64 << (s + 1)) - 1 };
+    let elem_mask = if size == 64 { u64::MAX } else { (1u64 << size) - 1 };
+    let rotated = if r == 0 {
+        welem
+    } else {
+        ((welem >> r) | (welem << (size - r))) & elem_mask
+    };
+    let mut mask = 0u64;
+    let mut offset = 0;
+    while offset < 64 {
+        mask |= rotated << offset;
+        offset += size;
+    }
+    if !is_64 {
+        mask &= 0xFFFF_FFFF;
+    }
+    Some(mask)
+}
+
+fn width_mask(is_64: bool) -> u64 {
+    if is_64 { u64::MAX } else { 0xFFFF_FFFF }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Writeback {
+    None,
+    // the base is updated before the access
+    Pre(i64),
+    // the base is updated after the access
+    Post(i64)
+}
+
+// The handful of things the emulator cares about
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Op {
+    Ret,
+    Set { rd: u32, value: u64 },
+    Movk { rd: u32, imm16: u64, shift: u32 },
+    OrrImm { rd: u32, rn: u32, mask: u64, width: u64 },
+    Mov { rd: u32, rm: u32, width: u64 },
+    AddImm { rd: u32, rn: u32, imm: u64 },
+    LoadLiteral { rt: u32, address: u64 },
+    Load { rt: u32, rn: u32, offset: i64, writeback: Writeback },
+    Compare { rn: u32, rm: u32 },
+    Call,
+    Jump,
+    // anything else, with every general purpose register it can write
+    Clobber([Option<u32>; 3])
+}
+
+fn decode(instr: u32, pc: u64) -> Op {
+    let decoded = match aarch64_decode::decode_a64(instr) {
+        Some(decoded) => decoded,
+        None => return Op::Clobber([None; 3])
+    };
+    match decoded {
+        Instr::Ret64RBranchReg => Op::Ret,
+        Instr::Movz64Movewide { imm16, Rd, .. } => Op::Set { rd: Rd as u32, value: (imm16 as u64) << movewide_shift(instr) },
+        Instr::Movz32Movewide { imm16, Rd, .. } => Op::Set { rd: Rd as u32, value: (imm16 as u64) << movewide_shift(instr) },
+        Instr::Movn64Movewide { imm16, Rd, .. } => Op::Set { rd: Rd as u32, value: !((imm16 as u64) << movewide_shift(instr)) },
+        Instr::Movn32Movewide { imm16, Rd, .. } => Op::Set { rd: Rd as u32, value: !((imm16 as u64) << movewide_shift(instr)) & width_mask(false) },
+        Instr::Movk64Movewide { imm16, Rd, .. } => Op::Movk { rd: Rd as u32, imm16: imm16 as u64, shift: movewide_shift(instr) },
+        Instr::Orr64LogImm { .. } | Instr::Orr32LogImm { .. } => {
+            let is_64 = instr >> 31 == 1;
+            match decode_bit_mask(is_64, (instr >> 22) & 1, (instr >> 10) & 0x3F, (instr >> 16) & 0x3F) {
+                Some(mask) => Op::OrrImm { rd: rd(instr), rn: rn(instr), mask, width: width_mask(is_64) },
+                None => Op::Clobber([Some(rd(instr)), None, None])
+            }
+        },
+        // MOV is ORR with ZR as the first source and no shift
+        Instr::Orr64LogShift { .. } | Instr::Orr32LogShift { .. } if rn(instr) == ZR && (instr >> 10) & 0x3F == 0 => {
+            Op::Mov { rd: rd(instr), rm: rm(instr), width: width_mask(instr >> 31 == 1) }
+        },
+        Instr::AdrpOnlyPcreladdr { .. } => {
+            let immlo = ((instr >> 29) & 0x3) as u64;
+            let immhi = ((instr >> 5) & 0x7FFFF) as u64;
+            let offset = sign_extend((immhi << 2) | immlo, 21) << 12;
+            Op::Set { rd: rd(instr), value: ((pc & !0xFFF) as i64 + offset) as u64 }
+        },
+        Instr::Add64AddsubImm { .. } => {
+            let shift = if (instr >> 22) & 1 == 1 { 12 } else { 0 };
+            Op::AddImm { rd: rd(instr), rn: rn(instr), imm: (((instr >> 10) & 0xFFF) as u64) << shift }
+        },
+        Instr::Ldr64Loadlit { .. } => {
+            let offset = sign_extend(((instr >> 5) & 0x7FFFF) as u64, 19) << 2;
+            Op::LoadLiteral { rt: rd(instr), address: (pc as i64 + offset) as u64 }
+        },
+        Instr::Ldr64LdstPos { .. } => Op::Load { rt: rd(instr), rn: rn(instr), offset: (((instr >> 10) & 0xFFF) as i64) * 8, writeback: Writeback::None },
+        Instr::Ldr64LdstImmpre { .. } => Op::Load { rt: rd(instr), rn: rn(instr), offset: 0, writeback: Writeback::Pre(imm9(instr)) },
+        Instr::Ldr64LdstImmpost { .. } => Op::Load { rt: rd(instr), rn: rn(instr), offset: 0, writeback: Writeback::Post(imm9(instr)) },
+        // CMP Xn, Xm is how every create_agent function checks the hash
+        Instr::Subs64AddsubShift { .. } if rd(instr) == ZR => Op::Compare { rn: rn(instr), rm: rm(instr) },
+        Instr::BlOnlyBranchImm { .. } | Instr::Blr64BranchReg { .. } => Op::Call,
+        // either a jump table or a tail call, neither leaves anything useful in the registers
+        Instr::Br64BranchReg { .. } => Op::Jump,
+        _ => Op::Clobber(register_writes(instr))
+    }
+}
+
+// Every general purpose register an instruction we don't emulate might write. Data processing instructions
+// all put Rd in the low bits, loads use the same field for Rt, and pre/post indexed loads and stores also
+// write back to their base register
+fn register_writes(instr: u32) -> [Option<u32>; 3] {
+    let op0 = (instr >> 25) & 0xF;
+    match op0 {
+        // data processing (immediate)
+        0b1000 | 0b1001 => [Some(rd(instr)), None, None],
+        // data processing (register)
+        0b0101 | 0b1101 => [Some(rd(instr)), None, None],
+        // loads and stores
+        0b0100 | 0b0110 | 0b1100 | 0b1110 => {
+            let is_load = (instr >> 22) & 1 == 1;
+            let is_pair = (instr >> 27) & 0x7 == 0b101;
+            let writeback = if is_pair {
+                // post, pre and signed offset pairs only differ in bits 23 and 24, bit 23 is set when writing back
+                (instr >> 23) & 1 == 1
+            } else {
+                // single register immediate forms with bits 11 and 10 as 01 (post) or 11 (pre)
+                instr & 0x3B200400 == 0x38000400
+            };
+            [
+                if is_load { Some(rd(instr)) } else { None },
+                if is_load && is_pair { Some(rt2(instr)) } else { None },
+                if writeback { Some(rn(instr)) } else { None }
+            ]
+        },
+        _ => [None; 3]
+    }
+}
+
+struct Cpu {
+    registers: [Option<u64>; REGISTER_COUNT]
+}
+
+impl Cpu {
+    fn new() -> Self {
+        Self {
+            registers: [None; REGISTER_COUNT]
+        }
+    }
+
+    fn get(&self, register: u32) -> Option<u64> {
+        if register == ZR {
+            Some(0)
+        } else {
+            self.registers[register as usize]
+        }
+    }
+
+    fn set(&mut self, register: u32, value: Option<u64>) {
+        if register != ZR {
+            self.registers[register as usize] = value;
+        }
+    }
+
+    // caller saved registers are garbage after a call
+    fn clobber_call(&mut self) {
+        for register in self.registers.iter_mut().take(19) {
+            *register = None;
+        }
+        self.registers[30] = None;
+    }
+}
+
+// `code` is the function body, `pc` the address of its first instruction, and `read_u64` is used for literal
+// pool and ADRP loads. Stops at the end of `code`, or at the first RET if `stop_at_ret` is set (for when the
+// function size isn't known)
+pub fn extract_hashes<F: Fn(u64) -> Option<u64>>(code: &[u32], pc: u64, stop_at_ret: bool, read_u64: F) -> ExtractedHashes {
+    let mut cpu = Cpu::new();
+    let mut hashes = Vec::new();
+    let mut is_complete = true;
+
+    for (index, instr) in code.iter().enumerate() {
+        let pc = pc + (index as u64) * 4;
+        match decode(*instr, pc) {
+            Op::Ret => {
+                if stop_at_ret {
+                    break;
+                }
+                cpu = Cpu::new();
+            },
+            Op::Set { rd, value } => cpu.set(rd, Some(value)),
+            Op::Movk { rd, imm16, shift } => {
+                let value = cpu.get(rd).map(|value| (value & !(0xFFFFu64 << shift)) | (imm16 << shift));
+                cpu.set(rd, value);
+            },
+            Op::OrrImm { rd, rn, mask, width } => {
+                let value = cpu.get(rn).map(|source| (source | mask) & width);
+                cpu.set(rd, value);
+            },
+            Op::Mov { rd, rm, width } => {
+                let value = cpu.get(rm).map(|value| value & width);
+                cpu.set(rd, value);
+            },
+            Op::AddImm { rd, rn, imm } => {
+                let value = cpu.get(rn).map(|value| value.wrapping_add(imm));
+                cpu.set(rd, value);
+            },
+            Op::LoadLiteral { rt, address } => cpu.set(rt, read_u64(address)),
+            Op::Load { rt, rn, offset, writeback } => {
+                let base = cpu.get(rn);
+                let address = match writeback {
+                    Writeback::Pre(imm) => base.map(|base| base.wrapping_add(imm as u64)),
+                    _ => base.map(|base| base.wrapping_add(offset as u64))
+                };
+                let value = address.and_then(|address| read_u64(address));
+                match writeback {
+                    Writeback::None => {},
+                    Writeback::Pre(_) => cpu.set(rn, address),
+                    Writeback::Post(imm) => cpu.set(rn, base.map(|base| base.wrapping_add(imm as u64)))
+                }
+                // the loaded value wins if it is the base register too
+                cpu.set(rt, value);
+            },
+            Op::Compare { rn, rm } => {
+                // one side is the hash that was asked for, which we never know. Known values that aren't
+                // hashes are just some other comparison
+                match (cpu.get(rn), cpu.get(rm)) {
+                    (None, None) => is_complete = false,
+                    (lhs, rhs) => {
+                        for value in lhs.into_iter().chain(rhs) {
+                            if looks_like_hash40(value) && !hashes.contains(&value) {
+                                hashes.push(value);
+                            }
+                        }
+                    }
+                }
+            },
+            Op::Call => cpu.clobber_call(),
+            Op::Jump => cpu = Cpu::new(),
+            Op::Clobber(registers) => {
+                for register in registers.iter().flatten() {
+                    cpu.set(*register, None);
+                }
+            }
+        }
+    }
+
+    ExtractedHashes {
+        hashes,
+        is_complete
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PC: u64 = 0x7100_0000;
+    const HASH_A: u64 = 0x5_1234_5678;
+    const HASH_B: u64 = 0x7_2222_1111;
+    const HASH_C: u64 = 0x9_0000_abcd;
+
+    fn no_memory(_: u64) -> Option<u64> {
+        None
+    }
+
+    // streams below were assembled with llvm-mc -triple=aarch64
+
+    #[test]
+    fn movz_movk_compares() {
+        let code = [
+            0xd28acf08, // mov x8, #0x5678
+            0xf2a24688, // movk x8, #0x1234, lsl #16
+            0xf2c000a8, // movk x8, #0x5, lsl #32
+            0xeb08001f, // cmp x0, x8
+            0x54000200, // b.eq #0x40
+            0xd2822229, // mov x9, #0x1111
+            0xf2a44449, // movk x9, #0x2222, lsl #16
+            0xf2c000e9, // movk x9, #0x7, lsl #32
+            0xeb09001f, // cmp x0, x9
+            0xd65f03c0, // ret
+        ];
+        let extracted = extract_hashes(&code, PC, true, no_memory);
+        assert_eq!(extracted.hashes, vec![HASH_A, HASH_B]);
+        assert!(extracted.is_complete);
+    }
+
+    #[test]
+    fn literal_pool_and_register_moves() {
+        let code = [
+            0x58000808, // ldr x8, #0x100
+            0xeb08001f, // cmp x0, x8
+            0xaa0803ea, // mov x10, x8
+            0xeb0a003f, // cmp x1, x10
+            0xd65f03c0, // ret
+        ];
+        let extracted = extract_hashes(&code, PC, true, |address| if address == PC + 0x100 { Some(HASH_A) } else { None });
+        assert_eq!(extracted.hashes, vec![HASH_A]);
+        assert!(extracted.is_complete);
+    }
+
+    #[test]
+    fn adrp_add_ldr() {
+        let code = [
+            0xd0000008, // adrp x8, #0x2000
+            0x91004108, // add x8, x8, #0x10
+            0xf9400509, // ldr x9, [x8, #8]
+            0xeb09001f, // cmp x0, x9
+            0xd65f03c0, // ret
+        ];
+        let table = PC + 0x2000 + 0x10;
+        let extracted = extract_hashes(&code, PC, true, |address| if address == table + 8 { Some(HASH_B) } else { None });
+        assert_eq!(extracted.hashes, vec![HASH_B]);
+        assert!(extracted.is_complete);
+    }
+
+    #[test]
+    fn pre_and_post_indexed_loads_write_back() {
+        let code = [
+            0xd0000008, // adrp x8, #0x2000
+            0x91004108, // add x8, x8, #0x10
+            0xf8408509, // ldr x9, [x8], #8
+            0xf940010a, // ldr x10, [x8]
+            0xeb09001f, // cmp x0, x9
+            0xeb0a001f, // cmp x0, x10
+            0xf8408d0b, // ldr x11, [x8, #8]!
+            0xeb0b001f, // cmp x0, x11
+            0xd65f03c0, // ret
+        ];
+        let table = PC + 0x2000 + 0x10;
+        let read = |address: u64| match address - table {
+            0 => Some(HASH_A),
+            8 => Some(HASH_B),
+            16 => Some(HASH_C),
+            _ => None
+        };
+        let extracted = extract_hashes(&code, PC, true, read);
+        assert_eq!(extracted.hashes, vec![HASH_A, HASH_B, HASH_C]);
+        assert!(extracted.is_complete);
+    }
+
+    #[test]
+    fn pair_writeback_loses_the_base() {
+        let code = [
+            0xd0000008, // adrp x8, #0x2000
+            0xa8c12909, // ldp x9, x10, [x8], #16
+            0xf940010b, // ldr x11, [x8]
+            0xeb0b001f, // cmp x0, x11
+            0xd65f03c0, // ret
+        ];
+        let extracted = extract_hashes(&code, PC, true, |_| Some(HASH_A));
+        assert!(extracted.hashes.is_empty());
+        assert!(!extracted.is_complete);
+    }
+
+    #[test]
+    fn store_writeback_loses_the_base() {
+        let code = [
+            0xd0000008, // adrp x8, #0x2000
+            0xf81f0d01, // str x1, [x8, #-16]!
+            0xf940010b, // ldr x11, [x8]
+            0xeb0b001f, // cmp x0, x11
+            0xd65f03c0, // ret
+        ];
+        let extracted = extract_hashes(&code, PC, true, |_| Some(HASH_A));
+        assert!(extracted.hashes.is_empty());
+        assert!(!extracted.is_complete);
+    }
+
+    #[test]
+    fn calls_clobber_caller_saved_registers() {
+        let code = [
+            0xd28acf08, // mov x8, #0x5678
+            0xf2a24688, // movk x8, #0x1234, lsl #16
+            0xf2c000a8, // movk x8, #0x5, lsl #32
+            0x94000040, // bl #0x100
+            0xeb08001f, // cmp x0, x8
+            0xd65f03c0, // ret
+        ];
+        let extracted = extract_hashes(&code, PC, true, no_memory);
+        assert!(extracted.hashes.is_empty());
+        assert!(!extracted.is_complete);
+    }
+
+    #[test]
+    fn sized_functions_continue_past_ret() {
+        let code = [
+            0xd28acf08, // mov x8, #0x5678
+            0xf2a24688, // movk x8, #0x1234, lsl #16
+            0xf2c000a8, // movk x8, #0x5, lsl #32
+            0xeb08001f, // cmp x0, x8
+            0xd65f03c0, // ret
+            0xd2822229, // mov x9, #0x1111
+            0xf2a44449, // movk x9, #0x2222, lsl #16
+            0xf2c000e9, // movk x9, #0x7, lsl #32
+            0xeb09001f, // cmp x0, x9
+            0xd65f03c0, // ret
+        ];
+        assert_eq!(extract_hashes(&code, PC, true, no_memory).hashes, vec![HASH_A]);
+        assert_eq!(extract_hashes(&code, PC, false, no_memory).hashes, vec![HASH_A, HASH_B]);
+    }
+
+    #[test]
+    fn unknown_writes_and_non_hash_compares() {
+        let code = [
+            0xd28acf08, // mov x8, #0x5678
+            0xf2a24688, // movk x8, #0x1234, lsl #16
+            0xf2c000a8, // movk x8, #0x5, lsl #32
+            0x8b010108, // add x8, x8, x1
+            0xeb08001f, // cmp x0, x8
+            0xd2800209, // mov x9, #0x10
+            0xeb09001f, // cmp x0, x9
+            0xd65f03c0, // ret
+        ];
+        let extracted = extract_hashes(&code, PC, true, no_memory);
+        assert!(extracted.hashes.is_empty());
+        assert!(!extracted.is_complete);
+    }
+
+    #[test]
+    fn register_writes_cover_writeback() {
+        // ldp x9, x10, [x8], #16
+        assert_eq!(register_writes(0xa8c12909), [Some(9), Some(10), Some(8)]);
+        // str x1, [x8, #-16]!
+        assert_eq!(register_writes(0xf81f0d01), [None, None, Some(8)]);
+        // ldr x10, [x8]
+        assert_eq!(register_writes(0xf940010a), [Some(10), None, None]);
+        // add x8, x8, x1
+        assert_eq!(register_writes(0x8b010108), [Some(8), None, None]);
+    }
+
+    #[test]
+    fn bit_masks() {
+        // mov w0, #0xff
+        assert_eq!(decode_bit_mask(false, 0, 0b000111, 0), Some(0xFF));
+        // mov x0, #0x5555555555555555
+        assert_eq!(decode_bit_mask(true, 0, 0b111100, 0), Some(0x5555_5555_5555_5555));
+        assert_eq!(decode_bit_mask(true, 1, 0b111111, 0), None);
+    }
+
+    #[test]
+    fn hash40_shape() {
+        assert!(looks_like_hash40(HASH_A));
+        assert!(!looks_like_hash40(0x1234_5678));
+        assert!(!looks_like_hash40(0x100_0000_0000));
+    }
+}
@@ -1,10 +1,12 @@
 use std::collections::HashMap;
+use std::ffi::c_void;
 
 use parking_lot::Mutex;
 
 use skyline::nro::NroInfo;
 use nnsdk::root::{Elf64_Sym, rtld::ModuleObject};
 
+use crate::abi::RegistrationError;
 use crate::c_str;
 use crate::rtld;
 
@@ -70,8 +72,21 @@ unsafe fn symbol_replace(module_object: *mut ModuleObject, symbol: *const Elf64_
     }
 }
 
+pub fn try_replace_symbol(module: &str, symbol: &str, replace: *const extern "C" fn(), original: Option<&'static mut *const extern "C" fn()>) -> Result<(), RegistrationError> {
+    if !crate::abi::accept_registration(replace as usize) {
+        return Err(RegistrationError::Refused);
+    }
+    add_symbol_hook(module, symbol, replace, original);
+    Ok(())
+}
+
 #[no_mangle]
 pub extern "Rust" fn replace_symbol(module: &str, symbol: &str, replace: *const extern "C" fn(), original: Option<&'static mut *const extern "C" fn()>) {
+    let _ = try_replace_symbol(module, symbol, replace, original);
+}
+
+// smashline's own hooks go through here so that smashline doesn't register itself as a plugin
+pub fn add_symbol_hook(module: &str, symbol: &str, replace: *const extern "C" fn(), original: Option<&'static mut *const extern "C" fn()>) {
     let mut map = SYMBOL_HOOKS.lock();
     let hook_ctx = HookCtx {
         symbol: String::from(symbol),
@@ -85,8 +100,25 @@ pub extern "Rust" fn replace_symbol(module: &str, symbol: &str, replace: *const
     }
 }
 
+// Hooks the function itself instead of its dynsym entry, so calls that never go through the symbol
+// (vtables, function pointers stored in L2CValues) are caught as well
+pub unsafe fn inline_symbol_hook(module_object: *mut ModuleObject, symbol: &str, replace: *const extern "C" fn(), original: &mut *const extern "C" fn()) {
+    let sym = rtld::get_symbol_by_name(module_object, symbol);
+    if sym.is_null() {
+        println!("[smashline::hooks] Unable to find symbol {} to hook", symbol);
+        return;
+    }
+    let address = ((*sym).st_value + (*module_object).module_base) as *const c_void;
+    let mut trampoline: *mut c_void = std::ptr::null_mut();
+    skyline::hooks::A64HookFunction(address, replace as *const c_void, &mut trampoline);
+    *original = trampoline as *const extern "C" fn();
+}
+
 #[no_mangle]
 pub extern "Rust" fn replace_static_symbol(symbol: StaticSymbol, replace: *const extern "C" fn(), mut original: Option<&'static mut *const extern "C" fn()>) {
+    if !crate::abi::accept_registration(replace as usize) {
+        return;
+    }
     unsafe {
         match symbol {
             StaticSymbol::Unresolved(sym) => {
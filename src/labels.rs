@@ -0,0 +1,92 @@
+use std::collections::HashMap;
+
+use smash::phx::Hash40;
+use parking_lot::Mutex;
+
+// ParamLabels.csv from the param-labels project, one `0x<hash>,<label>` per line
+pub const LABELS_PATH: &str = "sd:/atmosphere/contents/01006A800016E000/romfs/smashline/ParamLabels.csv";
+
+lazy_static! {
+    // names plugins and modules gave us, recording one has to stay cheap since it happens at boot
+    static ref REGISTERED: Mutex<HashMap<u64, String>> = Mutex::new(HashMap::new());
+    // the csv is large, it is only read the first time a label is actually looked up
+    static ref LABELS: Mutex<Option<HashMap<u64, String>>> = Mutex::new(None);
+}
+
+fn load_labels() -> HashMap<u64, String> {
+    let mut labels = HashMap::new();
+    let contents = match std::fs::read_to_string(LABELS_PATH) {
+        Ok(contents) => contents,
+        // the file is optional
+        Err(_) => return labels
+    };
+    for line in contents.lines() {
+        let mut split = line.splitn(2, ',');
+        let (hash, label) = match (split.next(), split.next()) {
+            (Some(hash), Some(label)) => (hash.trim(), label.trim()),
+            _ => continue
+        };
+        let hash = hash.trim_start_matches("0x").trim_start_matches("0X");
+        if let Ok(hash) = u64::from_str_radix(hash, 16) {
+            labels.insert(hash, String::from(label));
+        }
+    }
+    println!("[smashline::labels] Loaded {} hash labels", labels.len());
+    labels
+}
+
+pub fn record(label: &str) -> Hash40 {
+    let hash = Hash40::new(label);
+    REGISTERED.lock().entry(hash.hash).or_insert_with(|| String::from(label));
+    hash
+}
+
+pub fn get_label(hash: Hash40) -> Option<String> {
+    if hash.hash == Hash40::new("common").hash {
+        return Some(String::from("common"));
+    }
+    if let Some(label) = REGISTERED.lock().get(&hash.hash) {
+        return Some(label.clone());
+    }
+    LABELS.lock().get_or_insert_with(load_labels).get(&hash.hash).cloned()
+}
+
+// False if ParamLabels.csv is missing, in which case only the names plugins and modules gave us are known
+pub fn has_label_file() -> bool {
+    !LABELS.lock().get_or_insert_with(load_labels).is_empty()
+}
+
+pub fn registered_names() -> Vec<String> {
+    REGISTERED.lock().values().cloned().collect()
+}
+
+// For the crash handler, never blocks and never reads the csv
+pub fn try_get_label(hash: Hash40) -> Option<String> {
+    if let Some(label) = REGISTERED.try_lock()?.get(&hash.hash) {
+        return Some(label.clone());
+    }
+    LABELS.try_lock()?.as_ref()?.get(&hash.hash).cloned()
+}
+
+pub fn format_hash(hash: Hash40) -> String {
+    get_label(hash).unwrap_or_else(|| format!("{:#x}", hash.hash))
+}
+
+// agent/script, like mario/game_attack11
+pub fn format_script(agent: Hash40, script: Hash40) -> String {
+    format!("{}/{}", format_hash(agent), format_hash(script))
+}
+
+pub fn nro_load(info: &skyline::nro::NroInfo) {
+    // fighter modules are named after their main agent
+    if !info.name.is_empty() {
+        record(info.name);
+    }
+}
+
+#[no_mangle]
+pub extern "Rust" fn add_hash_labels(labels: &[&str]) {
+    for label in labels.iter() {
+        record(label);
+    }
+}
@@ -16,16 +16,29 @@ use skyline::{hook, install_hook};
 use skyline::nro::NroInfo;
 use smash::lib::LuaConst;
 
+mod abi;
 mod acmd;
 mod callbacks;
+mod capi;
+mod catalogue;
+mod crash;
+mod events;
+mod extract;
 mod hooks;
+mod labels;
 mod loader;
 mod nro_hook;
 mod nx;
+mod rcu;
 mod rtld;
 mod scripts;
+mod shims;
 mod status;
+mod storage;
+mod suggest;
 mod unwind;
+mod validate;
+mod vtable_pool;
 
 #[derive(Clone)]
 pub enum LuaConstant {
@@ -57,23 +70,29 @@ macro_rules! c_str {
 pub static mut COMMON_MEMORY_INFO: Option<nx::QueryMemoryResult> = None;
 
 fn nro_load(info: &NroInfo) {
-    callbacks::nro_load(info);   
-    hooks::nro_load(info);
-    acmd::nro_load(info);
-    status::nro_load(info);
+    // has to be set first, everything below that links or installs on common load checks for it
     if info.name == "common" {
         unsafe {
             COMMON_MEMORY_INFO = Some(nx::svc::query_memory((*info.module.ModuleObject).module_base as usize).expect("Unable to query common memory info."));
         }
     }
+    crash::nro_load(info);
+    labels::nro_load(info);
+    callbacks::nro_load(info);   
+    hooks::nro_load(info);
+    acmd::nro_load(info);
+    status::nro_load(info);
 }
 
 fn nro_unload(info: &NroInfo) {
     scripts::clear_loaded_agent(info);
+    scripts::release_create_agents(info);
+    crash::nro_unload(info);
     callbacks::nro_unload(info);
     hooks::nro_unload(info);
     acmd::nro_unload(info);
     status::nro_unload(info);
+    unwind::nro_unload(info);
 }
 
 extern "C" {
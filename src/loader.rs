@@ -34,6 +34,9 @@ lazy_static! {
 
 const NRR_SIZE: usize = std::mem::size_of::<NrrHeader>();
 
+// hardcode the path here. would like to use rom but nnsdk caches the rom contents when it's mounted ig
+pub const DEVELOPMENT_PLUGIN_PATH: &str = "sd:/atmosphere/contents/01006A800016E000/romfs/smashline/development.nro";
+
 impl DevelopmentPlugin {
     pub unsafe fn new(path: &str) -> Option<Self> {
         let path = PathBuf::from(path);
@@ -132,6 +135,23 @@ impl DevelopmentPlugin {
 
     }
 
+    // Plugins export their required ABI as `smashline_required_abi`, plugins built before the handshake existed don't
+    pub unsafe fn check_abi(&self) -> bool {
+        let mut required_abi = 0usize;
+        let rc = ro::LookupModuleSymbol(&mut required_abi, &self.nro_module, c_str!("smashline_required_abi"));
+        if rc != 0 || required_abi == 0 {
+            crate::abi::log_unverified("development plugin");
+            return true;
+        }
+        let required = &*(required_abi as *const crate::abi::AbiInfo);
+        if crate::abi::is_compatible(required) {
+            true
+        } else {
+            crate::abi::log_mismatch(required);
+            false
+        }
+    }
+
     pub unsafe fn install(&self) {
         let mut install_fn = 0usize;
         let rc = ro::LookupModuleSymbol(&mut install_fn, &self.nro_module, c_str!("smashline_install"));
@@ -163,12 +183,23 @@ impl DevelopmentPlugin {
         crate::callbacks::remove_fighter_frame_callbacks(range);
         crate::callbacks::remove_weapon_frame_callbacks(range);
         crate::callbacks::remove_agent_frame_main_callbacks(range);
+        crate::callbacks::remove_kind_frame_callbacks(range);
+        crate::callbacks::remove_frame_replacements(range);
+        crate::callbacks::remove_status_change_callbacks(range);
+        crate::events::remove_agent_event_callbacks(range);
+        crate::storage::remove_agent_storage_types(range);
         crate::callbacks::remove_fighter_init_callbacks(range);
         crate::callbacks::remove_agent_init_callbacks(range);
+        crate::callbacks::remove_item_callbacks(range);
         crate::acmd::remove_acmd_scripts(range);
         crate::status::remove_status_scripts(range);
         crate::unwind::unregister_skyline_plugin(range.0);
+        crate::abi::remove_refused_plugins(range);
 
+        self.unload();
+    }
+
+    pub unsafe fn unload(&mut self) {
         println!("[smashline::loader] Unloading the development plugin...");
         ro::UnloadModule(&mut self.nro_module);
         UnregisterModuleInfo(&mut self.nrr_info);
@@ -183,9 +214,24 @@ pub unsafe fn load_development_plugin() {
         plugin.uninstall();
         std::mem::forget(plugin);
     }
-    // hardcode the path here. would like to use rom but nnsdk caches the rom contents when it's mounted ig
-    if let Some(plugin) = DevelopmentPlugin::new("sd:/atmosphere/contents/01006A800016E000/romfs/smashline/development.nro") {
-        plugin.install();
-        *loaded = Some(plugin);
+    if let Some(mut plugin) = DevelopmentPlugin::new(DEVELOPMENT_PLUGIN_PATH) {
+        if plugin.check_abi() {
+            plugin.install();
+            *loaded = Some(plugin);
+        } else {
+            println!("[smashline::loader] Development plugin is not compatible with this smashline_hook, it will not be installed.");
+            plugin.unload();
+            std::mem::forget(plugin);
+        }
+    }
+}
+
+// Used by the crash reporter, so it can't wait on the lock if we crashed while (un)loading
+pub fn get_development_plugin_range() -> Option<(usize, usize)> {
+    let loaded = LOADED_DEVELOPMENT_PLUGIN.try_lock()?;
+    let plugin = loaded.as_ref()?;
+    unsafe {
+        let mem_info = crate::nx::svc::query_memory((*plugin.nro_module.ModuleObject).module_base as usize).ok()?;
+        Some((mem_info.mem_info.base_address, mem_info.mem_info.base_address + mem_info.mem_info.size))
     }
 }
\ No newline at end of file
@@ -0,0 +1,209 @@
+use std::ops::Deref;
+use std::sync::atomic::{AtomicPtr, AtomicUsize, Ordering};
+
+use parking_lot::Mutex;
+
+// Copy-on-write container for data that is read every frame but rarely changed.
+// Readers never block: they grab the current snapshot and keep it alive until the guard is dropped.
+// Writers clone the current snapshot, modify the clone and publish it, so a reader that is running user
+// code (which might register more callbacks) never holds anything a writer has to wait on.
+// Retired snapshots are only freed once there are no readers left.
+pub struct Rcu<T: Clone> {
+    current: AtomicPtr<T>,
+    readers: AtomicUsize,
+    retired: Mutex<Vec<Box<T>>>
+}
+
+pub struct RcuGuard<'a, T: Clone> {
+    rcu: &'a Rcu<T>,
+    value: &'a T
+}
+
+impl<T: Clone> Rcu<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            current: AtomicPtr::new(Box::into_raw(Box::new(value))),
+            readers: AtomicUsize::new(0),
+            retired: Mutex::new(Vec::new())
+        }
+    }
+
+    pub fn read(&self) -> RcuGuard<'_, T> {
+        // the reader count has to be visible before we load the snapshot, see `reclaim`
+        self.readers.fetch_add(1, Ordering::SeqCst);
+        let value = unsafe { &*self.current.load(Ordering::SeqCst) };
+        RcuGuard {
+            rcu: self,
+            value
+        }
+    }
+
+    pub fn update<R, F: FnOnce(&mut T) -> R>(&self, func: F) -> R {
+        let mut retired = self.retired.lock();
+        let mut new_value = unsafe { (*self.current.load(Ordering::SeqCst)).clone() };
+        let ret = func(&mut new_value);
+        let old_value = self.current.swap(Box::into_raw(Box::new(new_value)), Ordering::SeqCst);
+        retired.push(unsafe { Box::from_raw(old_value) });
+        Self::reclaim(&self.readers, &mut retired);
+        ret
+    }
+
+    // Any reader that registers after the swap can only see the new snapshot, so if nobody is reading
+    // right now then nobody can be holding a retired one
+    fn reclaim(readers: &AtomicUsize, retired: &mut Vec<Box<T>>) {
+        if readers.load(Ordering::SeqCst) == 0 {
+            retired.clear();
+        }
+    }
+}
+
+impl<'a, T: Clone> Deref for RcuGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<'a, T: Clone> Drop for RcuGuard<'a, T> {
+    fn drop(&mut self) {
+        if self.rcu.readers.fetch_sub(1, Ordering::SeqCst) == 1 {
+            // never block on the way out of a read, the next writer will clean up instead
+            if let Some(mut retired) = self.rcu.retired.try_lock() {
+                Rcu::<T>::reclaim(&self.rcu.readers, &mut retired);
+            }
+        }
+    }
+}
+
+unsafe impl<T: Clone + Send> Send for Rcu<T> {}
+unsafe impl<T: Clone + Send + Sync> Sync for Rcu<T> {}
+
+impl<T: Clone> Drop for Rcu<T> {
+    fn drop(&mut self) {
+        unsafe {
+            drop(Box::from_raw(*self.current.get_mut()));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    // counts how many clones are alive so the tests can see when retired snapshots get freed
+    #[derive(Clone)]
+    struct Tracked {
+        id: usize,
+        _alive: Arc<()>
+    }
+
+    fn alive(token: &Arc<()>) -> usize {
+        // minus the token itself
+        Arc::strong_count(token) - 1
+    }
+
+    #[test]
+    fn update_is_visible_to_new_readers() {
+        let rcu = Rcu::new(vec![1]);
+        rcu.update(|list| list.push(2));
+        assert_eq!(*rcu.read(), vec![1, 2]);
+    }
+
+    #[test]
+    fn update_returns_closure_result() {
+        let rcu = Rcu::new(5usize);
+        let old = rcu.update(|value| std::mem::replace(value, 6));
+        assert_eq!(old, 5);
+        assert_eq!(*rcu.read(), 6);
+    }
+
+    #[test]
+    fn reader_keeps_its_snapshot_across_updates() {
+        let rcu = Rcu::new(vec![1]);
+        let guard = rcu.read();
+        rcu.update(|list| list.push(2));
+        rcu.update(|list| list.push(3));
+        assert_eq!(*guard, vec![1]);
+        assert_eq!(*rcu.read(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn retired_snapshots_are_freed_without_readers() {
+        let token = Arc::new(());
+        let rcu = Rcu::new(Tracked { id: 0, _alive: token.clone() });
+        for index in 1..10 {
+            rcu.update(|tracked| tracked.id = index);
+            assert_eq!(alive(&token), 1);
+        }
+        assert_eq!(rcu.read().id, 9);
+    }
+
+    #[test]
+    fn retired_snapshots_outlive_readers() {
+        let token = Arc::new(());
+        let rcu = Rcu::new(Tracked { id: 0, _alive: token.clone() });
+        let guard = rcu.read();
+        rcu.update(|tracked| tracked.id = 1);
+        rcu.update(|tracked| tracked.id = 2);
+        // the guarded snapshot plus everything retired after it
+        assert_eq!(alive(&token), 3);
+        assert_eq!(guard.id, 0);
+        drop(guard);
+        // the last reader out reclaims
+        assert_eq!(alive(&token), 1);
+    }
+
+    #[test]
+    fn update_from_inside_a_read() {
+        let rcu = Rcu::new(vec![1]);
+        let guard = rcu.read();
+        for value in guard.iter() {
+            let value = *value;
+            rcu.update(|list| list.push(value + 1));
+        }
+        assert_eq!(*guard, vec![1]);
+        drop(guard);
+        assert_eq!(*rcu.read(), vec![1, 2]);
+    }
+
+    #[test]
+    fn dropping_frees_everything() {
+        let token = Arc::new(());
+        let rcu = Rcu::new(Tracked { id: 0, _alive: token.clone() });
+        rcu.update(|tracked| tracked.id = 1);
+        drop(rcu);
+        assert_eq!(alive(&token), 0);
+    }
+
+    #[test]
+    fn concurrent_readers_and_writers() {
+        let rcu = Arc::new(Rcu::new(vec![0usize]));
+        let writers: Vec<_> = (0..4).map(|_| {
+            let rcu = rcu.clone();
+            std::thread::spawn(move || {
+                for _ in 0..250 {
+                    rcu.update(|list| {
+                        let next = list.len();
+                        list.push(next);
+                    });
+                }
+            })
+        }).collect();
+        let readers: Vec<_> = (0..4).map(|_| {
+            let rcu = rcu.clone();
+            std::thread::spawn(move || {
+                for _ in 0..1000 {
+                    let guard = rcu.read();
+                    // every snapshot is a consistent 0..len run
+                    assert!(guard.iter().enumerate().all(|(index, value)| index == *value));
+                }
+            })
+        }).collect();
+        for thread in writers.into_iter().chain(readers) {
+            thread.join().unwrap();
+        }
+        assert_eq!(rcu.read().len(), 1001);
+    }
+}
@@ -85,6 +85,97 @@ struct Mod0 {
     pub module_object_offset: u32
 }
 
+// Every defined symbol in the module as (offset from module base, dynstr offset), sorted by offset so
+// lookups can binary search instead of walking the hash table
+pub unsafe fn collect_symbols(module_object: *const ModuleObject) -> Vec<(u64, u32)> {
+    let module_object = &*module_object;
+    let mut symbols = Vec::new();
+    for i in 0..module_object.hash_nbucket_value {
+        let mut j = *module_object.hash_bucket.offset(i as isize);
+        while j != 0 {
+            let sym = &*module_object.dynsym.offset(j as isize);
+            if sym.st_shndx != 0 && sym.st_value != 0 {
+                symbols.push((sym.st_value, sym.st_name));
+            }
+            j = *module_object.hash_chain.offset(j as isize);
+        }
+    }
+    symbols.sort_unstable();
+    symbols.dedup_by_key(|(value, _)| *value);
+    symbols
+}
+
+pub unsafe fn get_symbol_name(module_object: *const ModuleObject, name_offset: u32) -> String {
+    skyline::from_c_str((*module_object).dynstr.offset(name_offset as isize) as *const u8)
+}
+
+pub unsafe fn get_nearest_symbol(module_object: *const ModuleObject, address: usize) -> Option<(String, usize)> {
+    // just as slow as get_symbol_by_resolved_address, only use this for diagnostics
+    let module_object = &*module_object;
+    let difference = (address as u64).wrapping_sub(module_object.module_base);
+    let mut nearest: Option<&Elf64_Sym> = None;
+    for i in 0..module_object.hash_nbucket_value {
+        let mut j = *module_object.hash_bucket.offset(i as isize);
+        while j != 0 {
+            let sym = &*module_object.dynsym.offset(j as isize);
+            if sym.st_shndx != 0 && sym.st_value != 0 && sym.st_value <= difference {
+                if nearest.map_or(true, |current| current.st_value < sym.st_value) {
+                    nearest = Some(sym);
+                }
+            }
+            j = *module_object.hash_chain.offset(j as isize);
+        }
+    }
+    nearest.map(|sym| {
+        let name = skyline::from_c_str(module_object.dynstr.offset(sym.st_name as isize) as *const u8);
+        (name, (difference - sym.st_value) as usize)
+    })
+}
+
+unsafe fn try_get_mod0_from_address(address: usize) -> Option<(usize, Mod0)> {
+    let queried_mem = svc::query_memory(address).ok()?;
+    if !queried_mem.mem_info.permission.contains(nx::MemoryPermission::EXECUTE) {
+        return None;
+    }
+    let header = *(queried_mem.mem_info.base_address as *const Mod0Header);
+    if header.mod0_offset as usize >= queried_mem.mem_info.size {
+        return None;
+    }
+    let mod0_addr = queried_mem.mem_info.base_address + header.mod0_offset as usize;
+    let mod0 = *(mod0_addr as *const Mod0);
+    if mod0.magic != 0x30444f4d {
+        return None;
+    }
+    Some((mod0_addr, mod0))
+}
+
+// The whole module image, from the start of .text to the end of .bss. Querying the base address
+// alone only covers the first segment
+pub unsafe fn get_module_range(module_object: *const ModuleObject) -> Option<(usize, usize)> {
+    let module_base = (*module_object).module_base as usize;
+    let (mod0_addr, mod0) = try_get_mod0_from_address(module_base)?;
+    let end = mod0_addr + mod0.bss_end_offset as usize;
+    if end <= module_base {
+        return None;
+    }
+    Some((module_base, end))
+}
+
+pub unsafe fn try_get_module_object_from_address(address: usize) -> Option<*mut ModuleObject> {
+    // non-panicking version of get_module_object_from_address, for addresses that might not be in a module
+    let (mod0_addr, mod0) = try_get_mod0_from_address(address)?;
+    Some((mod0_addr + mod0.module_object_offset as usize) as *mut ModuleObject)
+}
+
+// Returns the range of the module's .eh_frame_hdr, if it was linked with one
+pub unsafe fn get_unwind_info_range(address: usize) -> Option<(usize, usize)> {
+    let (mod0_addr, mod0) = try_get_mod0_from_address(address)?;
+    if mod0.unwind_start_offset >= mod0.unwind_end_offset {
+        return None;
+    }
+    Some((mod0_addr + mod0.unwind_start_offset as usize, mod0_addr + mod0.unwind_end_offset as usize))
+}
+
 pub unsafe fn get_module_object_from_address(address: usize) -> Result<*mut ModuleObject, nx::NxResult> {
     let queried_mem = svc::query_memory(address)?;
     let header = *(queried_mem.mem_info.base_address as *const Mod0Header);
@@ -1,7 +1,6 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
 use nnsdk::root::{Elf64_Sym, rtld::ModuleObject};
 
-use aarch64_decode::*;
 use skyline::nro::NroInfo;
 use smash::phx::Hash40;
 use smash::app::{BattleObject, BattleObjectModuleAccessor};
@@ -14,6 +13,8 @@ use parking_lot::Mutex;
 use crate::hooks::lazy_symbol_replace;
 use crate::acmd::{Category, GAME_SCRIPTS, EFFECT_SCRIPTS, SOUND_SCRIPTS, EXPRESSION_SCRIPTS};
 use crate::status::{COMMON_STATUS_SCRIPTS, STATUS_SCRIPTS, STATUS_CUSTOMIZERS};
+use crate::events::AgentCategory;
+use crate::vtable_pool::VtablePool;
 use crate::COMMON_MEMORY_INFO;
 use Category::*;
 
@@ -31,34 +32,41 @@ macro_rules! generate_new_create_agent {
                 boma: *mut BattleObjectModuleAccessor,
                 state: *mut lua_State
             ) -> *mut L2CAgentBase {
-                let infos = $agent_infos.lock();
-                for (_, info) in infos.iter() {
-                    if info.hashes.contains(&hash) {
-                        let agent = (info.original)(hash, bobj, boma, state);
-                        LOADED_ACMD_AGENTS.lock().push(LoadedAcmdAgentInfo { agent: agent, hash: hash, category: $cat, is_share: $share });
-                        let mut script_list = $script_list.lock();
-                        if let Some(scripts) = script_list.get_mut(&Hash40::new("common")) {
-                            for script_info in scripts.iter_mut() {
-                                if let Some(original) = script_info.original.as_mut() {
-                                    **original = 0 as _;
-                                }
-                                (*agent).sv_set_function_hash(std::mem::transmute(script_info.bind_fn), script_info.script);
-                            }
+                let (module, original) = match $agent_infos.lock().get(hash) {
+                    Some(entry) => entry,
+                    None => return 0 as _
+                };
+                let agent = original(hash, bobj, boma, state);
+                let original_vtable = (*agent).vtable as *const u64;
+                let new_vtable = acquire_vtable(&module, hash, agent, original_vtable, || recreate_acmd_vtable(original_vtable, hash, $cat, $share));
+                (*agent).vtable = new_vtable as u64;
+                LOADED_ACMD_AGENTS.lock().push(LoadedAcmdAgentInfo { agent: agent, hash: hash, category: $cat, is_share: $share });
+                let mut script_list = $script_list.lock();
+                if let Some(scripts) = script_list.get_mut(&Hash40::new("common")) {
+                    for script_info in scripts.iter_mut().filter(|info| info.target.matches($share)) {
+                        if let Some(original) = script_info.original.as_mut() {
+                            **original = 0 as _;
                         }
-                        if let Some(scripts) = script_list.get_mut(&hash) {
-                            for script_info in scripts.iter_mut() {
-                                let og_func = *(*agent).functions.get(&script_info.script).unwrap_or(&(0 as _));
-                                script_info.backup = std::mem::transmute(og_func);
-                                if let Some(original) = script_info.original.as_mut() {
-                                    **original = std::mem::transmute(og_func);
-                                }
-                                (*agent).sv_set_function_hash(std::mem::transmute(script_info.bind_fn), script_info.script);
-                            }
+                        (*agent).sv_set_function_hash(std::mem::transmute(script_info.get_installed_fn()), script_info.script);
+                    }
+                }
+                if let Some(scripts) = script_list.get_mut(&hash) {
+                    for script_info in scripts.iter_mut().filter(|info| info.target.matches($share)) {
+                        let og_func = *(*agent).functions.get(&script_info.script).unwrap_or(&(0 as _));
+                        if og_func == 0 as _ {
+                            crate::validate::missing_acmd_script(agent, hash, script_info, $cat);
                         }
-                        return agent;
+                        script_info.set_backup(std::mem::transmute(og_func));
+                        if let Some(original) = script_info.original.as_mut() {
+                            **original = std::mem::transmute(og_func);
+                        }
+                        (*agent).sv_set_function_hash(std::mem::transmute(script_info.get_installed_fn()), script_info.script);
                     }
                 }
-                return 0 as _;
+                drop(script_list);
+                crate::validate::agent_created();
+                crate::events::agent_created(agent, hash, AgentCategory::Acmd($cat), $share);
+                agent
             }
         }
     }
@@ -75,13 +83,96 @@ generate_new_create_agent!(
     (animcmd_expression_share, EXPRESSION_SHARE_CREATE_AGENTS, EXPRESSION_SCRIPTS, ACMD_EXPRESSION, true)
 );
 
+const ACMD_DTOR: usize = 15;
+const ACMD_DEL_DTOR: usize = 16;
+const ACMD_AGENT_INFO: usize = 17;
+const ACMD_AGENT_HASH: usize = 18;
+
+// the category and share flag are packed into one vtable slot so the dtors can report them
+fn pack_acmd_info(category: Category, is_share: bool) -> u64 {
+    let category = match category {
+        ACMD_GAME => 0,
+        ACMD_EFFECT => 1,
+        ACMD_SOUND => 2,
+        ACMD_EXPRESSION => 3
+    };
+    category | ((is_share as u64) << 8)
+}
+
+fn unpack_acmd_info(info: u64) -> (Category, bool) {
+    let category = match info & 0xFF {
+        0 => ACMD_GAME,
+        1 => ACMD_EFFECT,
+        2 => ACMD_SOUND,
+        _ => ACMD_EXPRESSION
+    };
+    (category, (info >> 8) & 1 != 0)
+}
+
+unsafe fn recreate_acmd_vtable(vtable: *const u64, hash: Hash40, category: Category, is_share: bool) -> *const u64 {
+    let new_vtable = std::alloc::alloc(std::alloc::Layout::from_size_align(VTABLE_SIZE, 0x8).unwrap()) as *mut u64;
+    std::ptr::copy_nonoverlapping(vtable, new_vtable, 15);
+    *new_vtable.add(ACMD_DTOR) = *vtable.add(0);
+    *new_vtable.add(ACMD_DEL_DTOR) = *vtable.add(1);
+    *new_vtable.add(ACMD_AGENT_INFO) = pack_acmd_info(category, is_share);
+    *new_vtable.add(ACMD_AGENT_HASH) = hash.hash;
+    *new_vtable.add(0) = acmd_agent_dtor as *const () as u64;
+    *new_vtable.add(1) = acmd_agent_del_dtor as *const () as u64;
+    new_vtable
+}
+
+// Released panic shims can still be installed on live agents, once every recreated vtable has been given back
+// there is no agent left that could call one and they can be reused
+fn reclaim_shims_if_idle() {
+    if AGENT_VTABLES.lock().live_vtables() == 0 {
+        crate::shims::reclaim_retired();
+    }
+}
+
+// Both dtors get wrapped, but the deleting dtor might go through the other one, so only
+// the first one to see the agent reports it
+unsafe fn acmd_agent_destroyed(agent: *mut L2CAgentBase) {
+    let mut loaded_agents = LOADED_ACMD_AGENTS.lock();
+    let len = loaded_agents.len();
+    loaded_agents.retain(|info| info.agent != agent);
+    let was_loaded = loaded_agents.len() != len;
+    drop(loaded_agents);
+    if was_loaded {
+        let vtable = (*agent).vtable as *const u64;
+        let (category, is_share) = unpack_acmd_info(*vtable.add(ACMD_AGENT_INFO));
+        crate::events::agent_destroyed(agent, Hash40::new_raw(*vtable.add(ACMD_AGENT_HASH)), AgentCategory::Acmd(category), is_share);
+    }
+}
+
+unsafe extern "C" fn acmd_agent_dtor(agent: *mut L2CAgentBase) {
+    acmd_agent_destroyed(agent);
+    let vtable = (*agent).vtable as *const u64;
+    let claimed = AGENT_VTABLES.lock().take_agent(agent as usize);
+    let callable: extern "C" fn(*mut L2CAgentBase) = std::mem::transmute(*vtable.add(ACMD_DTOR));
+    callable(agent);
+    if let Some(vtable) = claimed {
+        release_vtable(vtable);
+    }
+}
+
+unsafe extern "C" fn acmd_agent_del_dtor(agent: *mut L2CAgentBase) {
+    acmd_agent_destroyed(agent);
+    let vtable = (*agent).vtable as *const u64;
+    let claimed = AGENT_VTABLES.lock().take_agent(agent as usize);
+    let callable: extern "C" fn(*mut L2CAgentBase) = std::mem::transmute(*vtable.add(ACMD_DEL_DTOR));
+    callable(agent);
+    if let Some(vtable) = claimed {
+        release_vtable(vtable);
+    }
+}
+
 const STATUS_DTOR: usize = 15;
 const STATUS_DEL_DTOR: usize = 16;
 const STATUS_SET: usize = 17;
 const STATUS_AGENT_HASH: usize = 18;
 
 unsafe fn recreate_status_vtable(vtable: *const u64, hash: Hash40) -> *const u64 {
-    let new_vtable = std::alloc::alloc(std::alloc::Layout::from_size_align(19 * 0x8, 0x8).unwrap()) as *mut u64;
+    let new_vtable = std::alloc::alloc(std::alloc::Layout::from_size_align(VTABLE_SIZE, 0x8).unwrap()) as *mut u64;
     std::ptr::copy_nonoverlapping(vtable, new_vtable, 15);
     *new_vtable.add(STATUS_DTOR) = *vtable.add(0);
     *new_vtable.add(STATUS_DEL_DTOR) = *vtable.add(1);
@@ -93,18 +184,47 @@ unsafe fn recreate_status_vtable(vtable: *const u64, hash: Hash40) -> *const u64
     new_vtable
 }
 
+unsafe fn get_status_agent_hash(agent: *mut L2CAgentBase) -> Hash40 {
+    Hash40::new_raw(*((*agent).vtable as *const u64).add(STATUS_AGENT_HASH))
+}
+
+unsafe fn status_agent_destroyed(agent: *mut L2CAgentBase) {
+    let mut loaded_agents = LOADED_STATUS_AGENTS.lock();
+    let len = loaded_agents.len();
+    loaded_agents.retain(|info| info.agent != agent);
+    let was_loaded = loaded_agents.len() != len;
+    drop(loaded_agents);
+    if was_loaded {
+        crate::callbacks::unhook_status_changes(agent);
+        crate::events::agent_destroyed(agent, get_status_agent_hash(agent), AgentCategory::Status, false);
+        crate::storage::clear_agent_storage((*(*agent).battle_object).battle_object_id);
+    }
+}
+
 unsafe extern "C" fn status_agent_dtor(agent: *mut L2CAgentBase) {
-    let callable: extern "C" fn(*mut L2CAgentBase) = std::mem::transmute(*((*agent).vtable as *const u64).add(STATUS_DTOR));
-    callable(agent)
+    status_agent_destroyed(agent);
+    let vtable = (*agent).vtable as *const u64;
+    let claimed = AGENT_VTABLES.lock().take_agent(agent as usize);
+    let callable: extern "C" fn(*mut L2CAgentBase) = std::mem::transmute(*vtable.add(STATUS_DTOR));
+    callable(agent);
+    if let Some(vtable) = claimed {
+        release_vtable(vtable);
+    }
 }
 
 unsafe extern "C" fn status_agent_del_dtor(agent: *mut L2CAgentBase) {
-    let callable: extern "C" fn(*mut L2CAgentBase) = std::mem::transmute(*((*agent).vtable as *const u64).add(STATUS_DEL_DTOR));
-    callable(agent)
+    status_agent_destroyed(agent);
+    let vtable = (*agent).vtable as *const u64;
+    let claimed = AGENT_VTABLES.lock().take_agent(agent as usize);
+    let callable: extern "C" fn(*mut L2CAgentBase) = std::mem::transmute(*vtable.add(STATUS_DEL_DTOR));
+    callable(agent);
+    if let Some(vtable) = claimed {
+        release_vtable(vtable);
+    }
 }
 
 unsafe extern "C" fn set_status_scripts(agent: *mut L2CAgentBase) {
-    let agent_hash = Hash40::new_raw(*((*agent).vtable as *const u64).add(STATUS_AGENT_HASH));
+    let agent_hash = get_status_agent_hash(agent);
     let callable: extern "C" fn(*mut L2CAgentBase) = std::mem::transmute(*((*agent).vtable as *const u64).add(STATUS_SET));
     callable(agent);
     let mut scripts = STATUS_SCRIPTS.lock();
@@ -114,10 +234,13 @@ unsafe extern "C" fn set_status_scripts(agent: *mut L2CAgentBase) {
                 &L2CValue::I32(script.status.get()),
                 &L2CValue::I32(script.condition.get())
             ).get_ptr();
+            if og_func.is_null() {
+                crate::validate::missing_status_script(agent_hash, script);
+            }
             if let Some(original) = script.original.as_mut() {
                 **original = std::mem::transmute(og_func);
             }
-            script.backup = std::mem::transmute(og_func);
+            script.set_backup(std::mem::transmute(og_func));
             let og = (*agent).sv_get_status_func(
                 &L2CValue::I32(script.status.get()),
                 &L2CValue::I32(script.condition.get())
@@ -125,7 +248,7 @@ unsafe extern "C" fn set_status_scripts(agent: *mut L2CAgentBase) {
             (*agent).sv_set_status_func(
                 L2CValue::I32(script.status.get()),
                 L2CValue::I32(script.condition.get()),
-                std::mem::transmute(script.replacement)
+                std::mem::transmute(script.get_installed_fn())
             );
         }
     }
@@ -140,6 +263,9 @@ unsafe extern "C" fn set_status_scripts(agent: *mut L2CAgentBase) {
         waza_info.backup = std::mem::transmute(og_func);
         (*(agent as *mut smash::lua2cpp::L2CFighterCommon)).global_table[0x3D].assign(&L2CValue::Ptr(waza_info.replacement as _));
     }
+    drop(customizers);
+    // last, so the dispatcher wraps whatever pre functions the replacements above installed
+    crate::callbacks::hook_status_changes(agent);
 }
 
 unsafe extern "C" fn create_agent_fighter_status_script(
@@ -148,34 +274,88 @@ unsafe extern "C" fn create_agent_fighter_status_script(
     boma: *mut BattleObjectModuleAccessor,
     state: *mut lua_State
 ) -> *mut L2CAgentBase {
-    let agents = STATUS_CREATE_AGENTS.lock();
-    for (module, info) in agents.iter() {
-        if info.hashes.contains(&hash) {
-            let agent = (info.original)(hash, bobj, boma, state);
-            let new_vtable = recreate_status_vtable((*agent).vtable as *const u64, hash);
-            (*agent).vtable = new_vtable as u64;
-            let mut vtables = STATUS_VTABLES.lock();
-            if let Some(vtables) = vtables.get_mut(module) {
-                vtables.push(new_vtable as u64);
-            } else {
-                vtables.insert(module.clone(), vec![new_vtable as u64]);
-            }
-            let mut loaded_agents = LOADED_STATUS_AGENTS.lock();
-            loaded_agents.push(LoadedStatusAgentInfo { agent, hash });
-            return agent;
-        }
-    }
-    0 as _
+    let (module, original) = match STATUS_CREATE_AGENTS.lock().get(hash) {
+        Some(entry) => entry,
+        None => return 0 as _
+    };
+    let agent = original(hash, bobj, boma, state);
+    let original_vtable = (*agent).vtable as *const u64;
+    let new_vtable = acquire_vtable(&module, hash, agent, original_vtable, || recreate_status_vtable(original_vtable, hash));
+    (*agent).vtable = new_vtable as u64;
+    LOADED_STATUS_AGENTS.lock().push(LoadedStatusAgentInfo { agent, hash });
+    crate::validate::agent_created();
+    crate::events::agent_created(agent, hash, AgentCategory::Status, false);
+    agent
 }
 
 type StatusFunc = unsafe extern "C" fn(*mut L2CAgentBase);
 type CreateAgentFunc = unsafe extern "C" fn(Hash40, *mut BattleObject, *mut BattleObjectModuleAccessor, *mut lua_State) -> *mut L2CAgentBase;
 
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct VtableKey {
+    pub module: String,
+    pub hash: u64,
+    pub original: u64
+}
+
 struct CreateAgentInfo {
     pub original: CreateAgentFunc,
     pub hashes: Vec<Hash40>
 }
 
+// Every module that creates agents of one category, plus an index of which module creates each agent
+// so the create_agent wrappers only have to do a single lookup
+struct CreateAgentTable {
+    pub name: &'static str,
+    pub modules: HashMap<String, CreateAgentInfo>,
+    pub by_hash: HashMap<u64, (String, CreateAgentFunc)>
+}
+
+impl CreateAgentTable {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            modules: HashMap::new(),
+            by_hash: HashMap::new()
+        }
+    }
+
+    fn index(&mut self, module: &String, original: CreateAgentFunc, hashes: &Vec<Hash40>) {
+        for hash in hashes.iter() {
+            if let Some((existing, _)) = self.by_hash.get(&hash.hash) {
+                if existing != module {
+                    println!("[smashline::scripts] Agent is created by more than one module, keeping the first | Table: {}, Agent: {}, Module: {}, Ignored: {}", self.name, crate::labels::format_hash(*hash), existing, module);
+                }
+                continue;
+            }
+            self.by_hash.insert(hash.hash, (module.clone(), original));
+        }
+    }
+
+    pub fn insert(&mut self, module: String, info: CreateAgentInfo) {
+        self.remove(&module);
+        self.index(&module, info.original, &info.hashes);
+        self.modules.insert(module, info);
+    }
+
+    pub fn remove(&mut self, module: &String) {
+        if self.modules.remove(module).is_none() {
+            return;
+        }
+        self.by_hash.retain(|_, (owner, _)| *owner != *module);
+        // anything that collided with the removed module can be indexed now
+        let modules: Vec<(String, CreateAgentFunc, Vec<Hash40>)> = self.modules.iter().map(|(name, info)| (name.clone(), info.original, info.hashes.clone())).collect();
+        for (name, original, hashes) in modules.iter() {
+            let unindexed: Vec<Hash40> = hashes.iter().filter(|hash| !self.by_hash.contains_key(&hash.hash)).copied().collect();
+            self.index(name, *original, &unindexed);
+        }
+    }
+
+    pub fn get(&self, hash: Hash40) -> Option<(String, CreateAgentFunc)> {
+        self.by_hash.get(&hash.hash).cloned()
+    }
+}
+
 #[derive(Copy, Clone)]
 struct LoadedAcmdAgentInfo {
     pub agent: *mut L2CAgentBase,
@@ -204,69 +384,64 @@ unsafe impl Sync for LoadedStatusAgentInfo {}
 unsafe impl Send for LoadedStatusAgentInfo {}
 
 lazy_static! {
-    static ref GAME_CREATE_AGENTS: Mutex<HashMap<String, CreateAgentInfo>> = Mutex::new(HashMap::new());
-    static ref GAME_SHARE_CREATE_AGENTS: Mutex<HashMap<String, CreateAgentInfo>> = Mutex::new(HashMap::new());
+    static ref GAME_CREATE_AGENTS: Mutex<CreateAgentTable> = Mutex::new(CreateAgentTable::new("game"));
+    static ref GAME_SHARE_CREATE_AGENTS: Mutex<CreateAgentTable> = Mutex::new(CreateAgentTable::new("game_share"));
     
-    static ref EFFECT_CREATE_AGENTS: Mutex<HashMap<String, CreateAgentInfo>> = Mutex::new(HashMap::new());
-    static ref EFFECT_SHARE_CREATE_AGENTS: Mutex<HashMap<String, CreateAgentInfo>> = Mutex::new(HashMap::new());
+    static ref EFFECT_CREATE_AGENTS: Mutex<CreateAgentTable> = Mutex::new(CreateAgentTable::new("effect"));
+    static ref EFFECT_SHARE_CREATE_AGENTS: Mutex<CreateAgentTable> = Mutex::new(CreateAgentTable::new("effect_share"));
     
-    static ref SOUND_CREATE_AGENTS: Mutex<HashMap<String, CreateAgentInfo>> = Mutex::new(HashMap::new());
-    static ref SOUND_SHARE_CREATE_AGENTS: Mutex<HashMap<String, CreateAgentInfo>> = Mutex::new(HashMap::new());
+    static ref SOUND_CREATE_AGENTS: Mutex<CreateAgentTable> = Mutex::new(CreateAgentTable::new("sound"));
+    static ref SOUND_SHARE_CREATE_AGENTS: Mutex<CreateAgentTable> = Mutex::new(CreateAgentTable::new("sound_share"));
     
-    static ref EXPRESSION_CREATE_AGENTS: Mutex<HashMap<String, CreateAgentInfo>> = Mutex::new(HashMap::new());
-    static ref EXPRESSION_SHARE_CREATE_AGENTS: Mutex<HashMap<String, CreateAgentInfo>> = Mutex::new(HashMap::new());
+    static ref EXPRESSION_CREATE_AGENTS: Mutex<CreateAgentTable> = Mutex::new(CreateAgentTable::new("expression"));
+    static ref EXPRESSION_SHARE_CREATE_AGENTS: Mutex<CreateAgentTable> = Mutex::new(CreateAgentTable::new("expression_share"));
 
-    static ref STATUS_CREATE_AGENTS: Mutex<HashMap<String, CreateAgentInfo>> = Mutex::new(HashMap::new());
-    static ref STATUS_VTABLES: Mutex<HashMap<String, Vec<u64>>> = Mutex::new(HashMap::new());
+    static ref STATUS_CREATE_AGENTS: Mutex<CreateAgentTable> = Mutex::new(CreateAgentTable::new("status"));
+    static ref AGENT_VTABLES: Mutex<VtablePool<VtableKey>> = Mutex::new(VtablePool::new());
 
     static ref LOADED_ACMD_AGENTS: Mutex<Vec<LoadedAcmdAgentInfo>> = Mutex::new(Vec::new());
     static ref LOADED_STATUS_AGENTS: Mutex<Vec<LoadedStatusAgentInfo>> = Mutex::new(Vec::new());
 }
 
-fn get_movk_offset(instr: u32) -> u32 {
-    (instr & 0x0060_0000) >> 21
-}
-
-pub fn read_hashes_from_executable(mut address: *const u32) -> Vec<Hash40> {
-    let mut hashes = Vec::new();
-    let mut cpu = [0; 31];
-
-    unsafe {
-        while let Some(instr) = aarch64_decode::decode_a64(*address) {
-            match instr {
-                Instr::Ret64RBranchReg => {
-                    break;
-                },
-                Instr::Movz64Movewide { imm16: imm, Rd: dst, .. } => {
-                    cpu[dst as usize] = imm as u64;
-                },
-                Instr::Movk64Movewide { imm16: part, Rd: dst, .. } => {
-                    let offset = get_movk_offset(*address) * 16;
-                    cpu[dst as usize] |= (part as u64) << offset;
-                },
-                Instr::Subs64AddsubShift{ Rm: src, Rn: _, Rd: _, .. } => {
-                    hashes.push(Hash40::new_raw(cpu[src as usize]));
-                },
-                _ => {}
+// The hashes that the create_agent function recognizes. Not being able to read all of them is an error, the
+// agents that were missed never get their replacements. Fighter modules are named after their main agent, so
+// that one at least is added back, the item module has no such name to fall back on
+unsafe fn read_create_agent_hashes(info: &NroInfo, symbol: &str, function: *const u32) -> Vec<Hash40> {
+    let sym = crate::rtld::get_symbol_by_name(info.module.ModuleObject as *const ModuleObject, symbol);
+    let size = if sym.is_null() { 0 } else { (*sym).st_size as usize / 4 };
+    let (code, stop_at_ret) = if size != 0 {
+        (std::slice::from_raw_parts(function, size), false)
+    } else {
+        (std::slice::from_raw_parts(function, crate::extract::MAX_UNSIZED_INSTRUCTIONS), true)
+    };
+    let read_u64 = |address: u64| {
+        if crate::crash::is_readable(address as usize) {
+            Some(*(address as *const u64))
+        } else {
+            None
+        }
+    };
+    let extracted = crate::extract::extract_hashes(code, function as u64, stop_at_ret, read_u64);
+    let mut hashes: Vec<Hash40> = extracted.hashes.iter().map(|hash| Hash40::new_raw(*hash)).collect();
+    if hashes.is_empty() || !extracted.is_complete {
+        println!("[smashline::scripts] ERROR: Unable to read every agent hash from create_agent function, replacements for the missing agents will not be installed | Module: {}, Symbol: {}, Found: {}", info.name, symbol, hashes.len());
+        if info.name != "item" {
+            let fallback = Hash40::new(info.name);
+            if !hashes.iter().any(|hash| hash.hash == fallback.hash) {
+                hashes.push(fallback);
             }
-            address = address.add(1);
         }
     }
-
-    let mut set = HashSet::new();
-    for hash in hashes.iter() {
-        set.insert(hash.hash);
-    }
-
-    let hashes: Vec<Hash40> = set.iter().map(|x| Hash40::new_raw(*x)).collect();
-    for hash in hashes.iter() {
-        // println!("{:#x}", hash.hash);
-    }
     hashes
 }
 
-fn format_create_agent_symbol(fighter: &str, func: &str) -> String {
-    let func_name = format!("create_agent_fighter_{}_{}", func, fighter);
+// Fighter modules have a create function per fighter, the item module has one for every item (including
+// assist trophies and pokeball pokemon), so it isn't suffixed with a name
+fn format_create_agent_symbol(module: &str, func: &str) -> String {
+    let func_name = match module {
+        "item" => format!("create_agent_item_{}", func),
+        fighter => format!("create_agent_fighter_{}_{}", func, fighter)
+    };
     format!("_ZN7lua2cpp{}{}EN3phx6Hash40EPN3app12BattleObjectEPNS2_26BattleObjectModuleAccessorEP9lua_State", func_name.len(), func_name)
 }
 
@@ -287,7 +462,7 @@ pub fn patch_create_agent_animcmd(info: &NroInfo, category: Category) {
                             String::from(info.name),
                             CreateAgentInfo {
                                 original: std::mem::transmute(ORIGINAL),
-                                hashes: read_hashes_from_executable(ORIGINAL as *const u32)
+                                hashes: read_create_agent_hashes(info, $cat.as_str(), ORIGINAL as *const u32)
                             }
                         );
                     }
@@ -299,7 +474,7 @@ pub fn patch_create_agent_animcmd(info: &NroInfo, category: Category) {
                             String::from(info.name),
                             CreateAgentInfo {
                                 original: std::mem::transmute(ORIGINAL),
-                                hashes: read_hashes_from_executable(ORIGINAL as *const u32)
+                                hashes: read_create_agent_hashes(info, [<$cat _share>].as_str(), ORIGINAL as *const u32)
                             }
                         );
                     }
@@ -337,43 +512,76 @@ pub fn patch_create_agent_status(info: &NroInfo) {
                 String::from(info.name),
                 CreateAgentInfo {
                     original: std::mem::transmute(ORIGINAL),
-                    hashes: read_hashes_from_executable(ORIGINAL as *const u32)
+                    hashes: read_create_agent_hashes(info, status.as_str(), ORIGINAL as *const u32)
                 }
             );
         }
     }
 }
 
-pub fn release_status_vtables(info: &NroInfo) {
-    let name = String::from(info.name);
-    let mut vtables = STATUS_VTABLES.lock();
-    if let Some(vtable_list) = vtables.remove(&name) {
-        unsafe {
-            let layout = std::alloc::Layout::from_size_align_unchecked(19 * 0x8, 0x8);
-            for vtable in vtable_list.into_iter() {
-                let vtable = vtable as *mut u8;
-                std::alloc::dealloc(vtable, layout);
-            }
-        }
+const VTABLE_SIZE: usize = 19 * 0x8;
+
+// Recreated vtables are shared between every agent created from the same original vtable, and freed
+// once the last of those agents has been destroyed, see vtable_pool.rs
+unsafe fn acquire_vtable<F: FnOnce() -> *const u64>(module: &String, hash: Hash40, agent: *mut L2CAgentBase, original: *const u64, recreate: F) -> *const u64 {
+    let key = VtableKey {
+        module: module.clone(),
+        hash: hash.hash,
+        original: original as u64
+    };
+    let (vtable, stale) = AGENT_VTABLES.lock().acquire(key, agent as usize, || recreate() as usize);
+    if let Some(stale) = stale {
+        free_vtable(stale);
+    }
+    vtable as *const u64
+}
+
+unsafe fn free_vtable(vtable: usize) {
+    std::alloc::dealloc(vtable as *mut u8, std::alloc::Layout::from_size_align_unchecked(VTABLE_SIZE, 0x8));
+}
+
+// Must only be called after the original dtor has returned, since that still runs with our vtable installed
+unsafe fn release_vtable(vtable: usize) {
+    let should_free = AGENT_VTABLES.lock().release(vtable);
+    if should_free {
+        free_vtable(vtable);
+        reclaim_shims_if_idle();
     }
 }
 
-pub fn install_live_acmd_scripts(agent_hash: Hash40, category: Category, info: &mut crate::acmd::ScriptInfo) {
+// Agents from the module can still be alive and pointing at its vtables, so only stop handing them out and
+// let the agents' own dtors free them
+pub fn release_agent_vtables(info: &NroInfo) {
+    let name = String::from(info.name);
+    AGENT_VTABLES.lock().orphan(|key| key.module == name);
+}
+
+// `replaced` are the low priority registrations this one is taking over from, agents running one of them get
+// the new one and keep the original it was falling back to
+pub fn install_live_acmd_scripts(agent_hash: Hash40, category: Category, info: &mut crate::acmd::ScriptInfo, replaced: &[&crate::acmd::ScriptInfo]) {
     let agents = LOADED_ACMD_AGENTS.lock();
     for agent in agents.iter() {
-        if agent.hash == agent_hash && agent.category == category {
+        if agent.hash == agent_hash && agent.category == category && info.target.matches(agent.is_share) {
             unsafe {
-                let test_func = *((*agent.agent).vtable as *const usize).add(1);
+                let test_func = *((*agent.agent).vtable as *const usize).add(ACMD_DEL_DTOR);
                 let original_module = crate::nx::svc::query_memory(test_func).expect("Smashline unable to query mem info from live agent.");
                 let og_begin = original_module.mem_info.base_address;
                 let og_end = og_begin + original_module.mem_info.size;
                 let current = *(*agent.agent).functions.get(&info.script).unwrap_or(&(0 as _));
-                if current == 0 as _ || (og_begin <= (current as usize) && (current as usize) < og_end) {
+                if current == 0 as _ {
+                    crate::validate::missing_acmd_script(agent.agent, agent_hash, info, category);
+                }
+                let replaced = replaced.iter().find(|replaced| replaced.get_installed_fn() as usize == current as usize);
+                if current == 0 as _ || (og_begin <= (current as usize) && (current as usize) < og_end) || replaced.is_some() {
+                    let vanilla: *const extern "C" fn() = match replaced {
+                        Some(replaced) => replaced.backup,
+                        None => std::mem::transmute(current)
+                    };
                     if let Some(original) = info.original.as_mut() {
-                        **original = std::mem::transmute(current);
+                        **original = vanilla;
                     }
-                    info.backup = std::mem::transmute(current);
-                    (*agent.agent).sv_set_function_hash(std::mem::transmute(info.bind_fn), info.script);
+                    info.set_backup(vanilla);
+                    (*agent.agent).sv_set_function_hash(std::mem::transmute(info.get_installed_fn()), info.script);
                 }
             }
         }
@@ -399,7 +607,8 @@ pub unsafe fn install_live_status_waza(agent_hash: Hash40, info: &mut crate::sta
     }
 }
 
-pub unsafe fn install_live_status_scripts(agent_hash: Hash40, info: &mut crate::status::StatusInfo, common_module: &crate::nx::QueryMemoryResult, is_common: bool) {
+pub unsafe fn install_live_status_scripts(agent_hash: Hash40, info: &mut crate::status::StatusInfo, common_module: &crate::nx::QueryMemoryResult, is_common: bool, replaced: Option<&crate::status::StatusInfo>) {
+    let replaced_fn = replaced.map(|replaced| replaced.get_installed_fn() as usize);
     let agents = LOADED_STATUS_AGENTS.lock();
     for agent in agents.iter() {
         if agent.hash == agent_hash || is_common {
@@ -407,21 +616,22 @@ pub unsafe fn install_live_status_scripts(agent_hash: Hash40, info: &mut crate::
             let original_module = crate::nx::svc::query_memory(test_func).expect("Smashline unable to query mem info from live agent.");
             let common = common_module.mem_info.base_address..common_module.mem_info.base_address + common_module.mem_info.size;
             let original = original_module.mem_info.base_address..(original_module.mem_info.base_address + original_module.mem_info.size);
-            let current = (*agent.agent).sv_get_status_func(
-                &L2CValue::I32(info.status.get()),
-                &L2CValue::I32(info.condition.get())
-            ).get_ptr() as usize;
+            let current = crate::callbacks::get_status_func(agent.agent, info.status.get(), info.condition.get());
+            if current == 0 && !is_common {
+                crate::validate::missing_status_script(agent_hash, info);
+            }
             // println!("{:#x} {:#x?} {:#x?}", current, common, original);
-            if current == 0 || common.contains(&current) || (original.contains(&current) && !is_common) {
+            let is_replaced = replaced_fn == Some(current);
+            if current == 0 || common.contains(&current) || (original.contains(&current) && !is_common) || is_replaced {
+                let vanilla: *const extern "C" fn() = match replaced {
+                    Some(replaced) if is_replaced => replaced.backup,
+                    _ => std::mem::transmute(current)
+                };
                 if let Some(original) = info.original.as_mut() {
-                    **original = std::mem::transmute(current);
+                    **original = vanilla;
                 }
-                info.backup = std::mem::transmute(current);
-                (*agent.agent).sv_set_status_func(
-                    L2CValue::I32(info.status.get()),
-                    L2CValue::I32(info.condition.get()),
-                    std::mem::transmute(info.replacement)
-                );
+                info.set_backup(vanilla);
+                crate::callbacks::set_status_func(agent.agent, info.status.get(), info.condition.get(), info.get_installed_fn() as usize);
             }
         }
     }
@@ -434,7 +644,7 @@ pub unsafe fn remove_live_acmd_scripts(range: (usize, usize)) {
             if let Some(script_list) = scripts.get(&$agent.hash) {
                 for script in script_list.iter() {
                     let as_usize = script.bind_fn as *const () as usize;
-                    if $begin <= as_usize && as_usize < $end {
+                    if $begin <= as_usize && as_usize < $end && script.target.matches($agent.is_share) {
                         (*$agent.agent).sv_set_function_hash(
                             std::mem::transmute(script.backup),
                             script.script
@@ -446,7 +656,8 @@ pub unsafe fn remove_live_acmd_scripts(range: (usize, usize)) {
     }
 
     let (begin, end) = range;
-    let agents = LOADED_ACMD_AGENTS.lock();
+    // registration locks the script lists before the agents, so don't hold the agents while taking them
+    let agents: Vec<LoadedAcmdAgentInfo> = LOADED_ACMD_AGENTS.lock().clone();
     for agent in agents.iter() {
         match agent.category {
             ACMD_GAME => {
@@ -478,7 +689,8 @@ pub unsafe fn remove_live_status_waza(range: (usize, usize)) {
 
 pub unsafe fn remove_live_status_scripts(range: (usize, usize)) {
     let (begin, end) = range;
-    let agents = LOADED_STATUS_AGENTS.lock();
+    // registration locks the script lists before the agents, so don't hold the agents while taking them
+    let agents: Vec<LoadedStatusAgentInfo> = LOADED_STATUS_AGENTS.lock().clone();
     let mut scripts = STATUS_SCRIPTS.lock();
     let mut common_scripts = COMMON_STATUS_SCRIPTS.lock();
     for agent in agents.iter() {
@@ -486,11 +698,7 @@ pub unsafe fn remove_live_status_scripts(range: (usize, usize)) {
             for script in script_list.iter_mut() {
                 let as_usize = script.replacement as *const () as usize;
                 if begin <= as_usize && as_usize < end {
-                    (*agent.agent).sv_set_status_func(
-                        L2CValue::I32(script.status.get()),
-                        L2CValue::I32(script.condition.get()),
-                        std::mem::transmute(script.backup)
-                    );
+                    crate::callbacks::set_status_func(agent.agent, script.status.get(), script.condition.get(), script.backup as usize);
                 }
             }
         }
@@ -498,11 +706,7 @@ pub unsafe fn remove_live_status_scripts(range: (usize, usize)) {
             for script in script_list.iter_mut () {
                 let as_usize = script.replacement as *const () as usize;
                 if begin <= as_usize && as_usize < end {
-                    (*agent.agent).sv_set_status_func(
-                        L2CValue::I32(script.status.get()),
-                        L2CValue::I32(script.condition.get()),
-                        std::mem::transmute(script.backup)
-                    );
+                    crate::callbacks::set_status_func(agent.agent, script.status.get(), script.condition.get(), script.backup as usize);
                 }
             }
         }
@@ -522,7 +726,7 @@ pub fn clear_loaded_agent(info: &NroInfo) {
     ];
     let module_name = String::from(info.name);
     for hashes in possible_agent_hashes.iter() {
-        if let Some(agent_hashes) = hashes.get(&module_name) {
+        if let Some(agent_hashes) = hashes.modules.get(&module_name) {
             for hash in agent_hashes.hashes.iter() {
                 let mut loaded_agents = LOADED_ACMD_AGENTS.lock();
                 let mut new_agents = Vec::new();
@@ -543,4 +747,52 @@ pub fn clear_loaded_agent(info: &NroInfo) {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+fn all_create_agent_tables() -> [&'static Mutex<CreateAgentTable>; 9] {
+    [
+        &*GAME_CREATE_AGENTS,
+        &*GAME_SHARE_CREATE_AGENTS,
+        &*EFFECT_CREATE_AGENTS,
+        &*EFFECT_SHARE_CREATE_AGENTS,
+        &*SOUND_CREATE_AGENTS,
+        &*SOUND_SHARE_CREATE_AGENTS,
+        &*EXPRESSION_CREATE_AGENTS,
+        &*EXPRESSION_SHARE_CREATE_AGENTS,
+        &*STATUS_CREATE_AGENTS
+    ]
+}
+
+pub fn get_known_agents() -> Vec<Hash40> {
+    let mut agents: Vec<u64> = Vec::new();
+    for table in all_create_agent_tables().iter() {
+        agents.extend(table.lock().by_hash.keys().copied());
+    }
+    agents.sort();
+    agents.dedup();
+    agents.into_iter().map(Hash40::new_raw).collect()
+}
+
+pub fn is_known_agent(agent_hash: Hash40) -> bool {
+    all_create_agent_tables().iter().any(|table| table.lock().get(agent_hash).is_some())
+}
+
+pub fn release_create_agents(info: &NroInfo) {
+    let module_name = String::from(info.name);
+    for table in all_create_agent_tables().iter() {
+        table.lock().remove(&module_name);
+    }
+}
+
+// Any live agent will do for reading what scripts an agent defines, they are all set up the same way
+pub fn get_live_acmd_agent(agent_hash: Hash40, category: Category) -> Option<*mut L2CAgentBase> {
+    LOADED_ACMD_AGENTS.lock().iter().find(|agent| agent.hash == agent_hash && agent.category == category).map(|agent| agent.agent)
+}
+
+pub fn get_live_status_agent(agent_hash: Hash40) -> Option<*mut L2CAgentBase> {
+    LOADED_STATUS_AGENTS.lock().iter().find(|agent| agent.hash == agent_hash).map(|agent| agent.agent)
+}
+
+pub fn get_live_status_agents() -> Vec<*mut L2CAgentBase> {
+    LOADED_STATUS_AGENTS.lock().iter().map(|agent| agent.agent).collect()
+}
@@ -0,0 +1,325 @@
+use std::any::Any;
+use std::cell::Cell;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+use parking_lot::Mutex;
+
+use smash::lua2cpp::L2CAgentBase;
+use smash::lib::L2CValue;
+use smash::phx::Hash40;
+
+// Every installed ACMD/status replacement gets routed through one of these shims so that a panic
+// inside of user code falls back to the vanilla function instead of taking the game down with it.
+// The game only ever passes the agent to these functions, so each registration needs its own shim
+// to know which replacement to call. Large modpacks register a few thousand scripts, so there are plenty.
+const SHIM_SLOT_COUNT: usize = 4096;
+
+// The replacements have to be C-unwind, a panic unwinding out of a plain extern "C" function aborts
+// before it ever reaches the catch_unwind in the shim
+type AcmdFunc = unsafe extern "C-unwind" fn(*mut L2CAgentBase, *mut u64) -> u64;
+// status functions take at most a few L2CValues after the agent, forward all of them as-is
+type StatusFunc = unsafe extern "C-unwind" fn(*mut L2CAgentBase, u64, u64, u64, u64) -> L2CValue;
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum ShimKind {
+    Acmd,
+    Status
+}
+
+#[derive(Clone, Copy)]
+pub enum ShimOwner {
+    Acmd { agent: Hash40, script: Hash40 },
+    Status { agent: Hash40 }
+}
+
+impl ShimOwner {
+    fn describe(&self) -> String {
+        match self {
+            ShimOwner::Acmd { agent, script } => crate::labels::format_script(*agent, *script),
+            ShimOwner::Status { agent } => format!("{} status script", crate::labels::format_hash(*agent))
+        }
+    }
+
+    // same as describe, but safe to call from the crash handler
+    fn try_describe(&self) -> String {
+        let format = |hash: Hash40| crate::labels::try_get_label(hash).unwrap_or_else(|| format!("{:#x}", hash.hash));
+        match self {
+            ShimOwner::Acmd { agent, script } => format!("{}/{}", format(*agent), format(*script)),
+            ShimOwner::Status { agent } => format!("{} status script", format(*agent))
+        }
+    }
+}
+
+// A released shim might still be installed on live agents, so it can't be handed out again until every
+// agent has been destroyed. Until then it keeps calling its fallback
+#[derive(PartialEq, Clone, Copy)]
+enum SlotState {
+    Free,
+    Used,
+    Retired
+}
+
+const EMPTY_SLOT: AtomicUsize = AtomicUsize::new(0);
+
+static ACMD_TARGETS: [AtomicUsize; SHIM_SLOT_COUNT] = [EMPTY_SLOT; SHIM_SLOT_COUNT];
+static ACMD_FALLBACKS: [AtomicUsize; SHIM_SLOT_COUNT] = [EMPTY_SLOT; SHIM_SLOT_COUNT];
+static STATUS_TARGETS: [AtomicUsize; SHIM_SLOT_COUNT] = [EMPTY_SLOT; SHIM_SLOT_COUNT];
+static STATUS_FALLBACKS: [AtomicUsize; SHIM_SLOT_COUNT] = [EMPTY_SLOT; SHIM_SLOT_COUNT];
+
+// Every slot needs its own monomorphized shim, the table is built by halving the slot range until each
+// half is a single slot
+macro_rules! collect_shims {
+    ($func:ident) => {{
+        let mut shims = Vec::with_capacity(SHIM_SLOT_COUNT);
+        collect_shims!(@4096 shims, $func, 0);
+        shims
+    }};
+    (@4096 $shims:ident, $func:ident, $base:expr) => {
+        collect_shims!(@2048 $shims, $func, $base);
+        collect_shims!(@2048 $shims, $func, $base + 2048);
+    };
+    (@2048 $shims:ident, $func:ident, $base:expr) => {
+        collect_shims!(@1024 $shims, $func, $base);
+        collect_shims!(@1024 $shims, $func, $base + 1024);
+    };
+    (@1024 $shims:ident, $func:ident, $base:expr) => {
+        collect_shims!(@512 $shims, $func, $base);
+        collect_shims!(@512 $shims, $func, $base + 512);
+    };
+    (@512 $shims:ident, $func:ident, $base:expr) => {
+        collect_shims!(@256 $shims, $func, $base);
+        collect_shims!(@256 $shims, $func, $base + 256);
+    };
+    (@256 $shims:ident, $func:ident, $base:expr) => {
+        collect_shims!(@128 $shims, $func, $base);
+        collect_shims!(@128 $shims, $func, $base + 128);
+    };
+    (@128 $shims:ident, $func:ident, $base:expr) => {
+        collect_shims!(@64 $shims, $func, $base);
+        collect_shims!(@64 $shims, $func, $base + 64);
+    };
+    (@64 $shims:ident, $func:ident, $base:expr) => {
+        collect_shims!(@32 $shims, $func, $base);
+        collect_shims!(@32 $shims, $func, $base + 32);
+    };
+    (@32 $shims:ident, $func:ident, $base:expr) => {
+        collect_shims!(@16 $shims, $func, $base);
+        collect_shims!(@16 $shims, $func, $base + 16);
+    };
+    (@16 $shims:ident, $func:ident, $base:expr) => {
+        collect_shims!(@8 $shims, $func, $base);
+        collect_shims!(@8 $shims, $func, $base + 8);
+    };
+    (@8 $shims:ident, $func:ident, $base:expr) => {
+        collect_shims!(@4 $shims, $func, $base);
+        collect_shims!(@4 $shims, $func, $base + 4);
+    };
+    (@4 $shims:ident, $func:ident, $base:expr) => {
+        collect_shims!(@2 $shims, $func, $base);
+        collect_shims!(@2 $shims, $func, $base + 2);
+    };
+    (@2 $shims:ident, $func:ident, $base:expr) => {
+        collect_shims!(@1 $shims, $func, $base);
+        collect_shims!(@1 $shims, $func, $base + 1);
+    };
+    (@1 $shims:ident, $func:ident, $base:expr) => {
+        $shims.push($func::<{ $base }> as *const () as usize);
+    };
+}
+
+// collect_shims! only knows how to build this many
+const _: () = assert!(SHIM_SLOT_COUNT == 4096);
+
+lazy_static! {
+    static ref ACMD_SHIMS: Vec<usize> = collect_shims!(acmd_shim);
+    static ref STATUS_SHIMS: Vec<usize> = collect_shims!(status_shim);
+
+    static ref ACMD_SLOTS: Mutex<Vec<SlotState>> = Mutex::new(vec![SlotState::Free; SHIM_SLOT_COUNT]);
+    static ref STATUS_SLOTS: Mutex<Vec<SlotState>> = Mutex::new(vec![SlotState::Free; SHIM_SLOT_COUNT]);
+
+    // what each slot is replacing, only formatted when something panics
+    static ref ACMD_OWNERS: Mutex<Vec<Option<ShimOwner>>> = Mutex::new(vec![None; SHIM_SLOT_COUNT]);
+    static ref STATUS_OWNERS: Mutex<Vec<Option<ShimOwner>>> = Mutex::new(vec![None; SHIM_SLOT_COUNT]);
+}
+
+thread_local! {
+    // the innermost replacement running on this thread, so a crash report can say which one it was
+    static RUNNING: Cell<Option<(ShimKind, usize)>> = Cell::new(None);
+}
+
+static ACMD_OUT_OF_SLOTS: AtomicBool = AtomicBool::new(false);
+static STATUS_OUT_OF_SLOTS: AtomicBool = AtomicBool::new(false);
+
+fn describe_panic(payload: &Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        String::from(*message)
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        String::from("<unknown panic payload>")
+    }
+}
+
+unsafe extern "C" fn acmd_shim<const SLOT: usize>(agent: *mut L2CAgentBase, variadic: *mut u64) -> u64 {
+    let target = ACMD_TARGETS[SLOT].load(Ordering::Acquire);
+    if target != 0 {
+        let callable: AcmdFunc = std::mem::transmute(target);
+        let previous = RUNNING.with(|running| running.replace(Some((ShimKind::Acmd, SLOT))));
+        let result = panic::catch_unwind(AssertUnwindSafe(|| callable(agent, variadic)));
+        RUNNING.with(|running| running.set(previous));
+        match result {
+            Ok(ret) => return ret,
+            Err(payload) => {
+                println!("[smashline::shims] ACMD script panicked, falling back to the original script | Script: {}, Function: {}, Panic: {}", describe_slot(ShimKind::Acmd, SLOT), crate::crash::describe_address(target), describe_panic(&payload));
+                ACMD_TARGETS[SLOT].store(0, Ordering::Release);
+            }
+        }
+    }
+    let fallback = ACMD_FALLBACKS[SLOT].load(Ordering::Acquire);
+    if fallback == 0 {
+        0
+    } else {
+        let callable: AcmdFunc = std::mem::transmute(fallback);
+        callable(agent, variadic)
+    }
+}
+
+unsafe extern "C" fn status_shim<const SLOT: usize>(agent: *mut L2CAgentBase, arg1: u64, arg2: u64, arg3: u64, arg4: u64) -> L2CValue {
+    let target = STATUS_TARGETS[SLOT].load(Ordering::Acquire);
+    if target != 0 {
+        let callable: StatusFunc = std::mem::transmute(target);
+        let previous = RUNNING.with(|running| running.replace(Some((ShimKind::Status, SLOT))));
+        let result = panic::catch_unwind(AssertUnwindSafe(|| callable(agent, arg1, arg2, arg3, arg4)));
+        RUNNING.with(|running| running.set(previous));
+        match result {
+            Ok(ret) => return ret,
+            Err(payload) => {
+                println!("[smashline::shims] Status script panicked, falling back to the original script | Script: {}, Function: {}, Panic: {}", describe_slot(ShimKind::Status, SLOT), crate::crash::describe_address(target), describe_panic(&payload));
+                STATUS_TARGETS[SLOT].store(0, Ordering::Release);
+            }
+        }
+    }
+    let fallback = STATUS_FALLBACKS[SLOT].load(Ordering::Acquire);
+    if fallback == 0 {
+        L2CValue::I32(0)
+    } else {
+        let callable: StatusFunc = std::mem::transmute(fallback);
+        callable(agent, arg1, arg2, arg3, arg4)
+    }
+}
+
+fn describe_slot(kind: ShimKind, slot: usize) -> String {
+    let owner = match kind {
+        ShimKind::Acmd => ACMD_OWNERS.lock()[slot],
+        ShimKind::Status => STATUS_OWNERS.lock()[slot]
+    };
+    owner.map(|owner| owner.describe()).unwrap_or_else(|| String::from("<unknown>"))
+}
+
+// The replacement running on the current thread, if any. Used while writing a crash report, so nothing here blocks
+pub fn try_describe_running() -> Option<String> {
+    let (kind, slot) = RUNNING.try_with(|running| running.get()).ok()??;
+    let owner = match kind {
+        ShimKind::Acmd => ACMD_OWNERS.try_lock()?[slot],
+        ShimKind::Status => STATUS_OWNERS.try_lock()?[slot]
+    };
+    owner.map(|owner| owner.try_describe())
+}
+
+fn get_slot_tables(kind: ShimKind) -> (&'static [AtomicUsize; SHIM_SLOT_COUNT], &'static [AtomicUsize; SHIM_SLOT_COUNT]) {
+    match kind {
+        ShimKind::Acmd => (&ACMD_TARGETS, &ACMD_FALLBACKS),
+        ShimKind::Status => (&STATUS_TARGETS, &STATUS_FALLBACKS)
+    }
+}
+
+fn lock_slots(kind: ShimKind) -> parking_lot::MutexGuard<'static, Vec<SlotState>> {
+    match kind {
+        ShimKind::Acmd => ACMD_SLOTS.lock(),
+        ShimKind::Status => STATUS_SLOTS.lock()
+    }
+}
+
+// `owner` is what shows up in the log if the replacement panics
+pub fn allocate(kind: ShimKind, target: *const extern "C" fn(), owner: ShimOwner) -> Option<usize> {
+    let mut slots = lock_slots(kind);
+    let slot = match slots.iter().position(|state| *state == SlotState::Free) {
+        Some(slot) => slot,
+        None => {
+            let out_of_slots = match kind {
+                ShimKind::Acmd => &ACMD_OUT_OF_SLOTS,
+                ShimKind::Status => &STATUS_OUT_OF_SLOTS
+            };
+            if !out_of_slots.swap(true, Ordering::Relaxed) {
+                println!("[smashline::shims] All {} panic shims are in use, replacements registered from now on are NOT protected against panics", SHIM_SLOT_COUNT);
+            }
+            println!("[smashline::shims] Out of panic shims, replacement will not be protected against panics | Script: {}", owner.describe());
+            return None;
+        }
+    };
+    slots[slot] = SlotState::Used;
+    match kind {
+        ShimKind::Acmd => ACMD_OWNERS.lock()[slot] = Some(owner),
+        ShimKind::Status => STATUS_OWNERS.lock()[slot] = Some(owner)
+    }
+    let (targets, fallbacks) = get_slot_tables(kind);
+    fallbacks[slot].store(0, Ordering::Release);
+    targets[slot].store(target as usize, Ordering::Release);
+    Some(slot)
+}
+
+// For shims that might have been installed on an agent. The fallback stays in place and the slot isn't
+// reused until reclaim_retired is called
+pub fn release(kind: ShimKind, slot: usize) {
+    let mut slots = lock_slots(kind);
+    let (targets, _) = get_slot_tables(kind);
+    targets[slot].store(0, Ordering::Release);
+    slots[slot] = SlotState::Retired;
+}
+
+// For shims that were never installed anywhere, like a registration that lost a priority conflict
+pub fn discard(kind: ShimKind, slot: usize) {
+    let mut slots = lock_slots(kind);
+    let (targets, fallbacks) = get_slot_tables(kind);
+    targets[slot].store(0, Ordering::Release);
+    fallbacks[slot].store(0, Ordering::Release);
+    slots[slot] = SlotState::Free;
+}
+
+// Called once no agents are alive, at which point nothing can still be pointing at a retired shim
+pub fn reclaim_retired() {
+    for kind in [ShimKind::Acmd, ShimKind::Status].iter() {
+        let mut slots = lock_slots(*kind);
+        let (_, fallbacks) = get_slot_tables(*kind);
+        for (slot, state) in slots.iter_mut().enumerate() {
+            if *state == SlotState::Retired {
+                fallbacks[slot].store(0, Ordering::Release);
+                *state = SlotState::Free;
+            }
+        }
+    }
+}
+
+pub fn set_fallback(kind: ShimKind, slot: usize, fallback: *const extern "C" fn()) {
+    let (_, fallbacks) = get_slot_tables(kind);
+    fallbacks[slot].store(fallback as usize, Ordering::Release);
+}
+
+pub fn get_shim(kind: ShimKind, slot: usize) -> *const extern "C" fn() {
+    match kind {
+        ShimKind::Acmd => ACMD_SHIMS[slot] as _,
+        ShimKind::Status => STATUS_SHIMS[slot] as _
+    }
+}
+
+// Used for the callbacks that smashline calls directly, returns false if the callback panicked
+pub fn call_guarded<F: FnOnce()>(callback: usize, func: F) -> bool {
+    match panic::catch_unwind(AssertUnwindSafe(func)) {
+        Ok(_) => true,
+        Err(payload) => {
+            println!("[smashline::shims] Callback {} panicked ({}), it will be removed", crate::crash::describe_address(callback), describe_panic(&payload));
+            false
+        }
+    }
+}
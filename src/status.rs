@@ -5,6 +5,8 @@ use std::collections::HashMap;
 use parking_lot::Mutex;
 use skyline::nro::NroInfo;
 use crate::LuaConstant;
+use crate::abi::RegistrationError;
+use crate::shims::{ShimKind, ShimOwner};
 
 lazy_static! {
     pub static ref STATUS_SCRIPTS: Mutex<HashMap<Hash40, Vec<StatusInfo>>> = Mutex::new(HashMap::new());
@@ -18,7 +20,8 @@ pub struct StatusInfo {
     pub original: Option<&'static mut *const extern "C" fn()>,
     pub low_priority: bool,
     pub replacement: *const extern "C" fn(),
-    pub backup: *const extern "C" fn()
+    pub backup: *const extern "C" fn(),
+    pub shim: Option<usize>
 }
 
 pub struct StatusWazaInfo {
@@ -36,7 +39,41 @@ impl StatusInfo {
             original: self.original.take(),
             low_priority: self.low_priority,
             replacement: self.replacement,
-            backup: self.backup
+            backup: self.backup,
+            shim: self.shim.take()
+        }
+    }
+
+    // agent, status kind and condition, by the agent's name when it has a label
+    pub fn describe(&mut self, agent: Hash40) -> String {
+        format!("Agent: {}, Status: {:#x}, Condition: {:#x}", crate::labels::format_hash(agent), self.status.get(), self.condition.get())
+    }
+
+    pub fn set_backup(&mut self, backup: *const extern "C" fn()) {
+        self.backup = backup;
+        if let Some(slot) = self.shim {
+            crate::shims::set_fallback(ShimKind::Status, slot, backup);
+        }
+    }
+
+    // the function that actually gets placed on the agent
+    pub fn get_installed_fn(&self) -> *const extern "C" fn() {
+        match self.shim {
+            Some(slot) => crate::shims::get_shim(ShimKind::Status, slot),
+            None => self.replacement
+        }
+    }
+
+    pub fn release_shim(&mut self) {
+        if let Some(slot) = self.shim.take() {
+            crate::shims::release(ShimKind::Status, slot);
+        }
+    }
+
+    // for a registration that was never installed on any agent
+    pub fn discard_shim(&mut self) {
+        if let Some(slot) = self.shim.take() {
+            crate::shims::discard(ShimKind::Status, slot);
         }
     }
 }
@@ -83,11 +120,11 @@ extern "C" fn sub_set_fighter_common_table_replace(fighter: &mut L2CFighterCommo
                     if let Some(original) = script.original.as_mut() {
                         **original = og;
                     }
-                    script.backup = og;
+                    script.set_backup(og);
                     fighter.sv_set_status_func(
                         L2CValue::I32(script.status.get()),
                         L2CValue::I32(script.condition.get()),
-                        std::mem::transmute(script.replacement)
+                        std::mem::transmute(script.get_installed_fn())
                     );
                 }
             }
@@ -97,7 +134,7 @@ extern "C" fn sub_set_fighter_common_table_replace(fighter: &mut L2CFighterCommo
 
 pub fn install() {
     unsafe {
-        crate::hooks::replace_symbol("common", "_ZN7lua2cpp16L2CFighterCommon28sub_set_fighter_common_tableEv", sub_set_fighter_common_table_replace as *const extern "C" fn(), Some(&mut ORIGINAL));
+        crate::hooks::add_symbol_hook("common", "_ZN7lua2cpp16L2CFighterCommon28sub_set_fighter_common_tableEv", sub_set_fighter_common_table_replace as *const extern "C" fn(), Some(&mut ORIGINAL));
     }
 }
 
@@ -124,7 +161,8 @@ pub fn nro_load(info: &NroInfo) {
                             let mut is_unique = true;
                             for high_info in high_priority.iter_mut() {
                                 if const_resolver(&mut high_info.status, &mut status_info.status) && const_resolver(&mut high_info.condition, &mut status_info.condition) {
-                                    println!("[smashline::status] Status script already replaced with high priority | Status: {:#x}, condition: {:#x}", high_info.status.get(), high_info.condition.get());
+                                    println!("[smashline::status] Status script already replaced with high priority | {}", status_info.describe(*agent));
+                                    status_info.discard_shim();
                                     is_unique = false;
                                     break;
                                 }
@@ -142,17 +180,22 @@ pub fn nro_load(info: &NroInfo) {
                         let mut is_unique = true;
                         for high_info in high_priority.iter_mut() {
                             if const_resolver(&mut high_info.status, &mut low_info.status) && const_resolver(&mut high_info.condition, &mut low_info.condition) {
-                                println!("[smashline::status] Status script already replaced with high priority | Status: {:#x}, condition: {:#x}", high_info.status.get(), high_info.condition.get());
+                                println!("[smashline::status] Status script already replaced with high priority | {}", low_info.describe(*agent));
+                                low_info.discard_shim();
                                 is_unique = false;
                                 break;
                             }
                         }
                         if is_unique {
-                            for output_info in output.iter_mut() {
-                                if const_resolver(&mut low_info.status, &mut output_info.status) && const_resolver(&mut low_info.condition, &mut output_info.status) {
+                            // the latest low priority registration wins, nothing was installed before common loaded
+                            // so the one it replaces never reached an agent
+                            let existing = output.iter_mut().find(|output_info| const_resolver(&mut low_info.status, &mut output_info.status) && const_resolver(&mut low_info.condition, &mut output_info.condition));
+                            match existing {
+                                Some(output_info) => {
+                                    output_info.discard_shim();
                                     *output_info = low_info;
-                                    break;
-                                }
+                                },
+                                None => output.push(low_info)
                             }
                         }
                     }
@@ -164,7 +207,7 @@ pub fn nro_load(info: &NroInfo) {
                 }
             }
         },
-        "item" | "" => {},
+        "" => {},
         _ => {
             crate::scripts::patch_create_agent_status(info);
         }
@@ -172,7 +215,7 @@ pub fn nro_load(info: &NroInfo) {
 }
 
 pub fn nro_unload(info: &NroInfo) {
-    crate::scripts::release_status_vtables(info);
+    crate::scripts::release_agent_vtables(info);
 }
 
 pub unsafe fn remove_status_scripts(range: (usize, usize)) {
@@ -187,6 +230,8 @@ pub unsafe fn remove_status_scripts(range: (usize, usize)) {
         for script in script_list.iter_mut() {
             if !range.contains(&(script.replacement as usize)) {
                 new_script_list.push(script.transfer());
+            } else {
+                script.release_shim();
             }
         }
         *script_list = new_script_list;
@@ -196,14 +241,19 @@ pub unsafe fn remove_status_scripts(range: (usize, usize)) {
         for script in script_list.iter_mut() {
             if !range.contains(&(script.replacement as usize)) {
                 new_script_list.push(script.transfer());
+            } else {
+                script.release_shim();
             }
         }
         *script_list = new_script_list;
     }
 }
 
-#[no_mangle]
-pub extern "Rust" fn replace_move_customizer(agent: Hash40, original: Option<&'static mut *const extern "C" fn()>, low_priority: bool, replacement: *const extern "C" fn()) {
+pub fn try_replace_move_customizer(agent: Hash40, original: Option<&'static mut *const extern "C" fn()>, low_priority: bool, replacement: *const extern "C" fn()) -> Result<(), RegistrationError> {
+    if !crate::abi::accept_registration(replacement as usize) {
+        return Err(RegistrationError::Refused);
+    }
+
     let mut info = StatusWazaInfo {
         original,
         replacement,
@@ -213,94 +263,132 @@ pub extern "Rust" fn replace_move_customizer(agent: Hash40, original: Option<&'s
 
     let mut customizers = STATUS_CUSTOMIZERS.lock();
 
-    unsafe {
-        crate::scripts::install_live_status_waza(agent, &mut info);
+    if let Some(waza_info) = customizers.get(&agent) {
+        if !waza_info.low_priority {
+            println!("[smashline::status] Status specializer (WAZA Customizer) has already been replaced and is not low priority | Agent: {}", crate::labels::format_hash(agent));
+            return Err(RegistrationError::Conflict);
+        }
     }
 
-    if let Some(waza_info) = customizers.get_mut(&agent) {
-        if (waza_info.low_priority) {
-            *waza_info = info.transfer();
-        } else {
-            println!("[smashline::status] Status specializer (WAZA Customizer) has already been replaced and is not low priority | Agent: {:#x}", agent.hash);
-        }
-    } else {
-        customizers.insert(agent, info);
+    unsafe {
+        crate::scripts::install_live_status_waza(agent, &mut info);
     }
+    customizers.insert(agent, info);
+    drop(customizers);
+    crate::validate::registration_added();
+    Ok(())
 }
 
 #[no_mangle]
-pub extern "Rust" fn replace_status_script(agent: Hash40, status: LuaConstant, condition: LuaConstant, original: Option<&'static mut *const extern "C" fn()>, low_priority: bool, replacement: *const extern "C" fn()) {
-    let mut info = StatusInfo {
+pub extern "Rust" fn replace_move_customizer(agent: Hash40, original: Option<&'static mut *const extern "C" fn()>, low_priority: bool, replacement: *const extern "C" fn()) {
+    let _ = try_replace_move_customizer(agent, original, low_priority, replacement);
+}
+
+pub fn try_replace_status_script(agent: Hash40, status: LuaConstant, condition: LuaConstant, original: Option<&'static mut *const extern "C" fn()>, low_priority: bool, replacement: *const extern "C-unwind" fn()) -> Result<(), RegistrationError> {
+    if !crate::abi::accept_registration(replacement as usize) {
+        return Err(RegistrationError::Refused);
+    }
+
+    // stored like the game's own functions, only the shim ever calls it and it does so as C-unwind
+    let replacement = replacement as *const extern "C" fn();
+    let info = StatusInfo {
         status,
         condition,
         original,
         low_priority,
         replacement,
-        backup: 0 as _
+        backup: 0 as _,
+        shim: crate::shims::allocate(ShimKind::Status, replacement, ShimOwner::Status { agent })
     };
+    add_status_script(agent, info)
+}
+
+#[no_mangle]
+pub extern "Rust" fn replace_status_script(agent: Hash40, status: LuaConstant, condition: LuaConstant, original: Option<&'static mut *const extern "C" fn()>, low_priority: bool, replacement: *const extern "C-unwind" fn()) {
+    let _ = try_replace_status_script(agent, status, condition, original, low_priority, replacement);
+}
 
+// The name is recorded as a label, so every message about the registration can use it
+#[no_mangle]
+pub extern "Rust" fn replace_status_script_by_name(agent: &str, status: LuaConstant, condition: LuaConstant, original: Option<&'static mut *const extern "C" fn()>, low_priority: bool, replacement: *const extern "C-unwind" fn()) {
+    let agent = crate::labels::record(agent);
+    let _ = try_replace_status_script(agent, status, condition, original, low_priority, replacement);
+}
+
+// Settles priority conflicts before anything is installed, so a registration that loses never reaches a live
+// agent. Returns the low priority registration being replaced, if there is one
+fn take_conflicting(agent: Hash40, script_list: &mut Vec<StatusInfo>, info: &mut StatusInfo, kind: &str) -> Result<Option<StatusInfo>, RegistrationError> {
+    let resolver = match unsafe { CONSTANT_RESOLVER.as_ref() } {
+        Some(resolver) => resolver,
+        // can't compare yet, the common load pass sorts these out
+        None => return Ok(None)
+    };
+    let existing = script_list.iter_mut().position(|script| (resolver)(&mut script.status, &mut info.status) && (resolver)(&mut script.condition, &mut info.condition));
+    match existing {
+        Some(index) if !script_list[index].low_priority => {
+            println!("[smashline::status] {} already replaced with high priority | {}", kind, info.describe(agent));
+            info.discard_shim();
+            Err(RegistrationError::Conflict)
+        },
+        Some(index) => Ok(Some(script_list.remove(index))),
+        None => Ok(None)
+    }
+}
+
+fn add_status_script(agent: Hash40, mut info: StatusInfo) -> Result<(), RegistrationError> {
     let mut scripts = STATUS_SCRIPTS.lock();
+    let script_list = scripts.entry(agent).or_insert_with(Vec::new);
+    let mut replaced = take_conflicting(agent, script_list, &mut info, "Status script")?;
     unsafe {
         if let Some(common_module) = crate::COMMON_MEMORY_INFO.as_ref() {
-            crate::scripts::install_live_status_scripts(agent, &mut info, common_module, false);
+            crate::scripts::install_live_status_scripts(agent, &mut info, common_module, false, replaced.as_ref());
         }
     }
-
-    if let Some(script_list) = scripts.get_mut(&agent) {
-        unsafe {
-            if let Some(resolver) = CONSTANT_RESOLVER.as_ref() {
-                for script in script_list.iter_mut() {
-                    if (resolver)(&mut script.status, &mut info.status) && (resolver)(&mut script.condition, &mut info.condition) {
-                        if script.low_priority {
-                            *script = info;
-                        } else {
-                            println!("[smashline::status] Status script already replaced with high priority | Status: {:#x}, condition: {:#x}", script.status.get(), script.condition.get());
-                        }
-                        return;
-                    }
-                }
-            }
-            script_list.push(info);
-        }
-    } else {
-        scripts.insert(agent, vec![info]);
+    if let Some(replaced) = replaced.as_mut() {
+        replaced.release_shim();
     }
+    script_list.push(info);
+    drop(scripts);
+    crate::validate::registration_added();
+    Ok(())
 }
 
-#[no_mangle]
-pub extern "Rust" fn replace_common_status_script(status: LuaConstant, condition: LuaConstant, original: Option<&'static mut *const extern "C" fn()>, replacement: *const extern "C" fn()) {
+pub fn try_replace_common_status_script(status: LuaConstant, condition: LuaConstant, original: Option<&'static mut *const extern "C" fn()>, replacement: *const extern "C-unwind" fn()) -> Result<(), RegistrationError> {
+    if !crate::abi::accept_registration(replacement as usize) {
+        return Err(RegistrationError::Refused);
+    }
+
+    // stored like the game's own functions, only the shim ever calls it and it does so as C-unwind
+    let replacement = replacement as *const extern "C" fn();
     let mut info = StatusInfo {
         status,
         condition,
         original,
         low_priority: false,
         replacement,
-        backup: 0 as _
+        backup: 0 as _,
+        shim: crate::shims::allocate(ShimKind::Status, replacement, ShimOwner::Status { agent: Hash40::new("common") })
     };
 
+    let common = Hash40::new("common");
     let mut scripts = COMMON_STATUS_SCRIPTS.lock();
+    let script_list = scripts.entry(common).or_insert_with(Vec::new);
+    let mut replaced = take_conflicting(common, script_list, &mut info, "Common status script")?;
     unsafe {
         if let Some(common_module) = crate::COMMON_MEMORY_INFO.as_ref() {
-            crate::scripts::install_live_status_scripts(Hash40::new("common"), &mut info, common_module, true);
+            crate::scripts::install_live_status_scripts(common, &mut info, common_module, true, replaced.as_ref());
         }
     }
-    if let Some(script_list) = scripts.get_mut(&Hash40::new("common")) {
-        unsafe {
-            if let Some(resolver) = CONSTANT_RESOLVER.as_ref() {
-                for script in script_list.iter_mut() {
-                    if (resolver)(&mut script.status, &mut info.status) && (resolver)(&mut script.condition, &mut info.condition) {
-                        if script.low_priority {
-                            *script = info;
-                        } else {
-                            println!("[smashline::status] Common status script already replaced with high priority | Status: {:#x}, condition: {:#x}", script.status.get(), script.condition.get());
-                        }
-                        return;
-                    }
-                }
-            }
-            script_list.push(info);
-        }
-    } else {
-        scripts.insert(Hash40::new("common"), vec![info]);
+    if let Some(replaced) = replaced.as_mut() {
+        replaced.release_shim();
     }
+    script_list.push(info);
+    drop(scripts);
+    crate::validate::registration_added();
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "Rust" fn replace_common_status_script(status: LuaConstant, condition: LuaConstant, original: Option<&'static mut *const extern "C" fn()>, replacement: *const extern "C-unwind" fn()) {
+    let _ = try_replace_common_status_script(status, condition, original, replacement);
 }
\ No newline at end of file
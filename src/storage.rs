@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::ffi::c_void;
+
+use parking_lot::Mutex;
+
+type StorageInit = fn() -> *mut c_void;
+type StorageDrop = fn(*mut c_void);
+
+struct StorageEntry {
+    pub data: usize,
+    pub drop: StorageDrop
+}
+
+lazy_static! {
+    // keyed by (battle object id, plugin chosen type id)
+    static ref AGENT_STORAGE: Mutex<HashMap<(u32, u64), StorageEntry>> = Mutex::new(HashMap::new());
+}
+
+// The drop functions are plugin code, so they run after the lock is released in case they touch storage themselves
+fn drop_entries(entries: Vec<StorageEntry>) {
+    for entry in entries.into_iter() {
+        let drop_fn = entry.drop;
+        crate::shims::call_guarded(drop_fn as *const () as usize, || drop_fn(entry.data as *mut c_void));
+    }
+}
+
+fn remove_where<F: Fn(&(u32, u64), &StorageEntry) -> bool>(should_remove: F) {
+    let mut storage = AGENT_STORAGE.lock();
+    let keys: Vec<(u32, u64)> = storage.iter().filter(|(key, entry)| should_remove(key, entry)).map(|(key, _)| *key).collect();
+    let removed = keys.iter().filter_map(|key| storage.remove(key)).collect();
+    drop(storage);
+    drop_entries(removed);
+}
+
+// Called from the RESET hooks and when a status agent is destroyed
+pub fn clear_agent_storage(battle_object_id: u32) {
+    remove_where(|(id, _), _| *id == battle_object_id);
+}
+
+pub fn remove_agent_storage_types(range: (usize, usize)) {
+    let range = range.0..range.1;
+    remove_where(|_, entry| range.contains(&(entry.drop as *const () as usize)));
+}
+
+// Returns the storage for this battle object and type id, creating it with `init` if it doesn't exist yet.
+// The pointer stays valid until the agent is reset or destroyed, at which point `drop` is called with it
+#[no_mangle]
+pub extern "Rust" fn get_agent_storage(battle_object_id: u32, type_id: u64, init: StorageInit, drop: StorageDrop) -> *mut c_void {
+    if let Some(entry) = AGENT_STORAGE.lock().get(&(battle_object_id, type_id)) {
+        return entry.data as *mut c_void;
+    }
+    // lookups are every frame, only creating storage counts as registering
+    if !crate::abi::accept_registration(drop as *const () as usize) {
+        return 0 as _;
+    }
+    let data = init();
+    let mut storage = AGENT_STORAGE.lock();
+    let entry = storage.entry((battle_object_id, type_id)).or_insert(StorageEntry { data: data as usize, drop });
+    if entry.data != data as usize {
+        // someone else created it while init was running
+        let existing = entry.data as *mut c_void;
+        std::mem::drop(storage);
+        drop_entries(vec![StorageEntry { data: data as usize, drop }]);
+        return existing;
+    }
+    data
+}
+
+#[no_mangle]
+pub extern "Rust" fn has_agent_storage(battle_object_id: u32, type_id: u64) -> bool {
+    AGENT_STORAGE.lock().contains_key(&(battle_object_id, type_id))
+}
+
+#[no_mangle]
+pub extern "Rust" fn remove_agent_storage(battle_object_id: u32, type_id: u64) {
+    let entry = AGENT_STORAGE.lock().remove(&(battle_object_id, type_id));
+    if let Some(entry) = entry {
+        drop_entries(vec![entry]);
+    }
+}
+
+// FNV-1a of the type's name. Unlike TypeId this is the same in every plugin that names the same type, so
+// plugins sharing a type share its storage
+pub const fn storage_type_id(type_name: &str) -> u64 {
+    let bytes = type_name.as_bytes();
+    let mut hash = 0xcbf29ce484222325u64;
+    let mut index = 0;
+    while index < bytes.len() {
+        hash ^= bytes[index] as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+        index += 1;
+    }
+    hash
+}
+
+fn init_typed<T: Default>() -> *mut c_void {
+    Box::into_raw(Box::new(T::default())) as *mut c_void
+}
+
+fn drop_typed<T>(data: *mut c_void) {
+    unsafe {
+        std::mem::drop(Box::from_raw(data as *mut T));
+    }
+}
+
+// Typed front end over get_agent_storage for Rust callers. init and drop are instantiated in whichever crate
+// calls this, so storage created from a plugin is still owned by and cleaned up with that plugin
+#[allow(dead_code)]
+pub unsafe fn get_typed_agent_storage<T: Default>(battle_object_id: u32) -> Option<&'static mut T> {
+    let type_id = storage_type_id(std::any::type_name::<T>());
+    let data = get_agent_storage(battle_object_id, type_id, init_typed::<T>, drop_typed::<T>) as *mut T;
+    data.as_mut()
+}
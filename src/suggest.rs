@@ -0,0 +1,78 @@
+// "Did you mean" matching for the validation messages, kept to plain strings so it can be tested without the game
+
+const MAX_SUGGESTIONS: usize = 3;
+
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + if a_char == *b_char { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+// The candidates close enough to `name` to likely be what was meant, closest first. `name` itself is never
+// suggested, it is what wasn't found
+pub fn closest<S: AsRef<str>>(name: &str, candidates: &[S]) -> Vec<String> {
+    let max_distance = (name.chars().count() / 3).max(2);
+    let mut matches: Vec<(usize, &str)> = candidates.iter()
+        .map(|candidate| candidate.as_ref())
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    matches.sort_unstable();
+    matches.dedup();
+    matches.into_iter().take(MAX_SUGGESTIONS).map(|(_, candidate)| String::from(candidate)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn distances() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("mario", "mario"), 0);
+        assert_eq!(edit_distance("mario", "maria"), 1);
+        assert_eq!(edit_distance("game_attack11", "game_atack11"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "luigi"), 5);
+    }
+
+    #[test]
+    fn closest_first() {
+        let candidates = ["game_attack12", "game_attack11", "game_attack13", "game_attackdash"];
+        assert_eq!(closest("game_atack11", &candidates), vec!["game_attack11", "game_attack12", "game_attack13"]);
+    }
+
+    #[test]
+    fn limited_to_three() {
+        let candidates = ["mariod", "marioo", "marioa", "mariob", "marioc"];
+        assert_eq!(closest("mario", &candidates).len(), 3);
+    }
+
+    #[test]
+    fn far_names_are_not_suggested() {
+        let candidates = ["donkey", "pikachu", "game_specialhi"];
+        assert!(closest("mario", &candidates).is_empty());
+    }
+
+    #[test]
+    fn the_name_itself_is_not_suggested() {
+        let candidates = vec![String::from("mario"), String::from("mariod"), String::from("mariod")];
+        assert_eq!(closest("mario", &candidates), vec!["mariod"]);
+    }
+
+    #[test]
+    fn short_names_allow_two_edits() {
+        assert_eq!(closest("ike", &["ice", "kirby"]), vec!["ice"]);
+        assert_eq!(closest("lnk", &["link"]), vec!["link"]);
+    }
+}
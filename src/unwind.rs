@@ -1,8 +1,11 @@
 use skyline::{nn, libc};
 use skyline::hooks::InlineCtx;
+use skyline::nro::NroInfo;
 
 use parking_lot::Mutex;
 
+use std::sync::atomic::{AtomicUsize, Ordering};
+
 use crate::c_str;
 use crate::nx::{self, svc};
 
@@ -16,6 +19,12 @@ const _URC_FATAL_PHASE1_ERROR: u64 = 3;
 const _URC_HANDLER_FOUND: u64 = 6;
 const _URC_INSTALL_CONTEXT: u64 = 7;
 
+const DW_EH_PE_UDATA4: u8 = 0x03;
+const DW_EH_PE_SDATA4: u8 = 0x0B;
+const DW_EH_PE_DATAREL: u8 = 0x30;
+
+const EH_MARKER: u32 = 0xB000B1E5;
+
 extern "C" {
     #[link_name = "\u{1}_Unwind_GetIP"]
     fn _Unwind_GetIP(context: *const u64) -> u64;
@@ -58,6 +67,8 @@ static mut BAD_INFO_CHECK_ADDRESS: usize = 0;
 
 static OFFSET_INIT: std::sync::Once = std::sync::Once::new();
 
+static EH_MARKER_SEARCH_DISTANCE: AtomicUsize = AtomicUsize::new(0x2000);
+
 lazy_static! {
     static ref CUSTOM_EH_MEM: Mutex<Vec<nx::QueryMemoryResult>> = Mutex::new(Vec::new());
 }
@@ -65,6 +76,7 @@ lazy_static! {
 #[skyline::hook(replace = libc::abort)]
 fn abort_hook() -> ! {
     println!("[smashline::unwind | Fatal Error] abort() has been called. Flushing logger.");
+    crate::crash::write_crash_report("abort() has been called");
     std::thread::sleep(std::time::Duration::from_millis(500));
 
     call_original!()
@@ -105,6 +117,17 @@ fn is_custom_eh_mem(ip: usize) -> Option<(usize, usize)> {
     None
 }
 
+// Only used for diagnostics, which may run while the unwinder is holding the lock
+pub fn get_skyline_plugin_range(ip: usize) -> Option<(usize, usize)> {
+    let custom_mems = CUSTOM_EH_MEM.try_lock()?;
+    for mem in custom_mems.iter() {
+        if mem.mem_info.base_address <= ip && ip < (mem.mem_info.base_address + mem.mem_info.size) {
+            return Some((mem.mem_info.base_address, mem.mem_info.base_address + mem.mem_info.size));
+        }
+    }
+    None
+}
+
 unsafe fn byte_search(start: *const u32, want: u32, distance: usize) -> Option<*const u32> {
     for x in 0..distance {
         let cur = start.add(x);
@@ -115,6 +138,154 @@ unsafe fn byte_search(start: *const u32, want: u32, distance: usize) -> Option<*
     None
 }
 
+unsafe fn read_uleb128(cursor: &mut *const u8) -> u64 {
+    let mut result = 0u64;
+    let mut shift = 0;
+    loop {
+        let byte = **cursor;
+        *cursor = cursor.add(1);
+        if shift < 64 {
+            result |= ((byte & 0x7F) as u64) << shift;
+        }
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return result;
+        }
+    }
+}
+
+// Size of a pointer in the given DW_EH_PE encoding, None for encodings we don't handle
+fn encoded_size(encoding: u8) -> Option<usize> {
+    match encoding & 0x0F {
+        0x00 | 0x04 | 0x0C => Some(8),
+        0x03 | 0x0B => Some(4),
+        0x02 | 0x0A => Some(2),
+        _ => None
+    }
+}
+
+unsafe fn read_encoded_width(address: *const u8, size: usize) -> u64 {
+    match size {
+        2 => std::ptr::read_unaligned(address as *const u16) as u64,
+        4 => std::ptr::read_unaligned(address as *const u32) as u64,
+        _ => std::ptr::read_unaligned(address as *const u64)
+    }
+}
+
+// Returns (length of the entry, pointer to the first byte after the length). Handles the 64 bit extended length
+unsafe fn read_entry_length(entry: *const u8) -> (u64, *const u8) {
+    let length = std::ptr::read_unaligned(entry as *const u32);
+    if length == 0xFFFF_FFFF {
+        (std::ptr::read_unaligned(entry.add(4) as *const u64), entry.add(12))
+    } else {
+        (length as u64, entry.add(4))
+    }
+}
+
+// The pointer encoding FDEs using this CIE store pc_begin and pc_range in, from the 'R' augmentation
+unsafe fn get_fde_encoding(cie: *const u8) -> Option<u8> {
+    let (length, mut cursor) = read_entry_length(cie);
+    if length == 0 {
+        return None;
+    }
+    // CIE id, always 4 bytes in .eh_frame
+    cursor = cursor.add(4);
+    let version = *cursor;
+    cursor = cursor.add(1);
+    let augmentation = cursor;
+    let mut augmentation_len = 0;
+    while *augmentation.add(augmentation_len) != 0 {
+        augmentation_len += 1;
+    }
+    let augmentation = std::slice::from_raw_parts(augmentation, augmentation_len);
+    cursor = cursor.add(augmentation_len + 1);
+    if augmentation.first() != Some(&b'z') {
+        // without augmentation data the FDE pointers are absolute
+        return Some(0x00);
+    }
+    // code alignment (uleb), data alignment (sleb, same byte layout), return address register
+    read_uleb128(&mut cursor);
+    read_uleb128(&mut cursor);
+    if version == 1 {
+        cursor = cursor.add(1);
+    } else {
+        read_uleb128(&mut cursor);
+    }
+    read_uleb128(&mut cursor);
+    for character in augmentation[1..].iter() {
+        match character {
+            b'R' => return Some(*cursor),
+            b'P' => {
+                let encoding = *cursor;
+                cursor = cursor.add(1 + encoded_size(encoding)?);
+            },
+            b'L' => cursor = cursor.add(1),
+            b'S' | b'B' => {},
+            _ => return None
+        }
+    }
+    Some(0x00)
+}
+
+// Checks the module's .eh_frame_hdr (pointed to by MOD0) for an FDE covering the IP.
+// Only the header encodings that lld emits for aarch64 are understood, anything else skips the lookup
+unsafe fn has_unwind_info(ip: usize) -> bool {
+    let (begin, end) = match crate::rtld::get_unwind_info_range(ip) {
+        Some(range) => range,
+        None => return false
+    };
+    if end - begin < 12 {
+        return false;
+    }
+    let header = begin as *const u8;
+    let version = *header;
+    let eh_frame_ptr_enc = *header.add(1) & 0x0F;
+    let fde_count_enc = *header.add(2);
+    let table_enc = *header.add(3);
+    if version != 1
+    || (eh_frame_ptr_enc != DW_EH_PE_UDATA4 && eh_frame_ptr_enc != DW_EH_PE_SDATA4)
+    || fde_count_enc != DW_EH_PE_UDATA4
+    || table_enc != (DW_EH_PE_DATAREL | DW_EH_PE_SDATA4)
+    {
+        return false;
+    }
+    let fde_count = *(header.add(8) as *const u32) as usize;
+    if 12 + fde_count * 8 > end - begin {
+        return false;
+    }
+    let table = header.add(12) as *const i32;
+    let entry_location = |index: usize| (begin as isize + *table.add(index * 2) as isize) as usize;
+
+    // find the last entry that starts at or before the ip
+    let (mut low, mut high) = (0, fde_count);
+    while low < high {
+        let mid = (low + high) / 2;
+        if entry_location(mid) <= ip {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+    if low == 0 {
+        return false;
+    }
+    let pc_begin = entry_location(low - 1);
+    let fde = (begin as isize + *table.add((low - 1) * 2 + 1) as isize) as *const u8;
+    let (length, cie_pointer) = read_entry_length(fde);
+    if length == 0 {
+        return false;
+    }
+    // the CIE pointer is relative to its own location
+    let cie = cie_pointer.sub(std::ptr::read_unaligned(cie_pointer as *const u32) as usize);
+    let size = match get_fde_encoding(cie).and_then(encoded_size) {
+        Some(size) => size,
+        None => return false
+    };
+    // pc_range follows pc_begin and uses the same width, but is never relative
+    let pc_range = read_encoded_width(cie_pointer.add(4 + size), size) as usize;
+    ip < pc_begin + pc_range
+}
+
 #[allow(unused_assignments)]
 unsafe extern "C" fn custom_eh_personality(version: i32, actions: u64, _: u64, _: *mut u64, context: *mut u64) -> u64 {
     let mut ret = _URC_FATAL_PHASE1_ERROR;
@@ -153,11 +324,15 @@ unsafe fn step_replace(this: *mut u64) -> u64 {
     let result = step_with_dwarf(address_space, ip, unwind_info, registers);
     if result == UNW_STEP_SUCCESS {
         let ip = _Unwind_GetIP(this) as usize;
-        if let Some(range) = is_custom_eh_mem(ip) {
-            let pc_end = match byte_search(ip as *const u32, 0xB000B1E5, 0x2000) {
+        let custom_range = match is_custom_eh_mem(ip) {
+            Some(range) if !has_unwind_info(ip) => Some(range),
+            _ => None
+        };
+        if let Some(range) = custom_range {
+            let pc_end = match byte_search(ip as *const u32, EH_MARKER, EH_MARKER_SEARCH_DISTANCE.load(Ordering::Relaxed)) {
                 Some(val) => val,
                 None => {
-                    panic!("Stack unwinding passing through skyline plugin with no eh marker. Address range: ({:#x} - {:#x})", range.0, range.1);
+                    panic!("Stack unwinding passing through skyline plugin with no unwind info or eh marker. Address range: ({:#x} - {:#x})", range.0, range.1);
                 }
             };
             let unwind_info_ptr = this.add(0x44);
@@ -179,7 +354,7 @@ unsafe fn step_replace(this: *mut u64) -> u64 {
 unsafe fn prevent_bad_info_check(ctx: &mut InlineCtx) {
     fn stub() {}
     let ip = _Unwind_GetIP(*ctx.registers[0].x.as_ref() as *const u64) as usize;
-    if let Some(_) = is_custom_eh_mem(ip) {
+    if is_custom_eh_mem(ip).is_some() && !has_unwind_info(ip) {
         *ctx.registers[8].x.as_mut() = std::mem::transmute(stub as *const ());
     }
 }
@@ -214,6 +389,23 @@ pub fn unregister_skyline_plugin(base_addr: usize) {
     *custom_mem = new_mems;
 }
 
+// Lets plugins opt into EH support before they register anything, pass any address inside of the plugin
+#[no_mangle]
+pub extern "Rust" fn register_plugin(addr: usize) {
+    crate::abi::accept_registration(addr);
+}
+
+pub fn nro_unload(info: &NroInfo) {
+    unsafe {
+        unregister_skyline_plugin((*info.module.ModuleObject).module_base as usize);
+    }
+}
+
+#[no_mangle]
+pub extern "Rust" fn set_eh_marker_search_distance(distance: usize) {
+    EH_MARKER_SEARCH_DISTANCE.store(distance, Ordering::Relaxed);
+}
+
 pub fn install() {
     OFFSET_INIT.call_once(|| {
         unsafe {
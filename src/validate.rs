@@ -0,0 +1,142 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use smash::lua2cpp::L2CAgentBase;
+use smash::phx::Hash40;
+use parking_lot::Mutex;
+
+use crate::acmd::{Category, ScriptInfo, GAME_SCRIPTS, EFFECT_SCRIPTS, SOUND_SCRIPTS, EXPRESSION_SCRIPTS};
+use crate::status::{StatusInfo, STATUS_SCRIPTS, STATUS_CUSTOMIZERS};
+
+// Replacements that can never match anything fail silently otherwise, so point them out. Each problem is
+// only reported once per session since agents are created constantly.
+
+lazy_static! {
+    static ref REPORTED: Mutex<HashSet<(u64, u64)>> = Mutex::new(HashSet::new());
+}
+
+fn should_report(agent: Hash40, item: Hash40) -> bool {
+    REPORTED.lock().insert((agent.hash, item.hash))
+}
+
+fn category_name(category: Category) -> &'static str {
+    match category {
+        Category::ACMD_GAME => "game",
+        Category::ACMD_EFFECT => "effect",
+        Category::ACMD_SOUND => "sound",
+        Category::ACMD_EXPRESSION => "expression"
+    }
+}
+
+// Labels for the candidates when there are any, otherwise every name we were given by a by-name registration
+// or a module, which at least catches typos between plugins
+fn candidate_names(candidates: &[Hash40]) -> Vec<String> {
+    let labels: Vec<String> = candidates.iter().filter_map(|candidate| crate::labels::get_label(*candidate)).collect();
+    if labels.is_empty() {
+        crate::labels::registered_names()
+    } else {
+        labels
+    }
+}
+
+fn report_missing_labels() {
+    static REPORTED_MISSING_LABELS: AtomicBool = AtomicBool::new(false);
+    if !REPORTED_MISSING_LABELS.swap(true, Ordering::Relaxed) {
+        println!("[smashline::validate] {} is missing, suggestions are limited to names registered by plugins", crate::labels::LABELS_PATH);
+    }
+}
+
+// The closest candidates to the name the hash was registered with, only works if we know that name
+fn suggest(hash: Hash40, candidates: &[Hash40]) -> Option<String> {
+    if !crate::labels::has_label_file() {
+        report_missing_labels();
+    }
+    let name = match crate::labels::get_label(hash) {
+        Some(name) => name,
+        None => {
+            println!("[smashline::validate] No suggestions, {:#x} has no known name. Register by name or add it to {}", hash.hash, crate::labels::LABELS_PATH);
+            return None;
+        }
+    };
+    let suggestions = crate::suggest::closest(&name, &candidate_names(candidates));
+    if suggestions.is_empty() {
+        None
+    } else {
+        Some(suggestions.join(", "))
+    }
+}
+
+// Called when an agent with replacements is created without the script being replaced in its function table.
+// This isn't necessarily wrong, new scripts can be added, but usually it's a typo
+pub fn missing_acmd_script(agent_ptr: *mut L2CAgentBase, agent: Hash40, info: &ScriptInfo, category: Category) {
+    let script = info.script;
+    if should_report(agent, script) {
+        println!("[smashline::validate] Replaced ACMD script is not defined by the agent, it will only run if something calls it by name | Script: {}, Category: {}", info.describe(agent), category_name(category));
+        let defined = unsafe { crate::catalogue::read_function_hashes(agent_ptr) };
+        if let Some(suggestions) = suggest(script, &defined) {
+            println!("[smashline::validate] Did you mean: {}", suggestions);
+        }
+    }
+}
+
+// Called when an agent is created without the status function being replaced, or when it is replaced on a
+// live agent that doesn't have it. Nothing will ever call it unless the agent changes to that status itself
+pub fn missing_status_script(agent: Hash40, info: &mut StatusInfo) {
+    // hashes are 40 bits, so the top bit keeps these apart from the ACMD script keys
+    let key = Hash40::new_raw(1 << 63 | (info.status.get() as u32 as u64) << 32 | info.condition.get() as u32 as u64);
+    if should_report(agent, key) {
+        println!("[smashline::validate] Replaced status script is not defined by the agent | {}", info.describe(agent));
+    }
+}
+
+fn registered_agents() -> Vec<Hash40> {
+    let mut agents = Vec::new();
+    for scripts in [&*GAME_SCRIPTS, &*EFFECT_SCRIPTS, &*SOUND_SCRIPTS, &*EXPRESSION_SCRIPTS].iter() {
+        agents.extend(scripts.lock().keys().copied());
+    }
+    agents.extend(STATUS_SCRIPTS.lock().keys().copied());
+    agents.extend(STATUS_CUSTOMIZERS.lock().keys().copied());
+    let common = Hash40::new("common");
+    let mut seen = HashSet::new();
+    agents.retain(|agent| agent.hash != common.hash && seen.insert(agent.hash));
+    agents
+}
+
+// Only meaningful once every fighter module is loaded, which is the case by the time any agent gets created
+pub fn validate_agents() -> usize {
+    let mut unknown = 0;
+    for agent in registered_agents().into_iter() {
+        if !crate::scripts::is_known_agent(agent) {
+            unknown += 1;
+            if should_report(agent, agent) {
+                println!("[smashline::validate] Scripts were replaced for an agent that no module creates | Agent: {}", crate::labels::format_hash(agent));
+                if let Some(suggestions) = suggest(agent, &crate::scripts::get_known_agents()) {
+                    println!("[smashline::validate] Did you mean: {}", suggestions);
+                }
+            }
+        }
+    }
+    unknown
+}
+
+static VALIDATED: AtomicBool = AtomicBool::new(false);
+
+pub fn agent_created() {
+    if !VALIDATED.swap(true, Ordering::SeqCst) {
+        validate_agents();
+    }
+}
+
+// Registrations made after the first agent was created (development reloads, late plugins) are checked as
+// they come in, must be called without any of the script locks held
+pub fn registration_added() {
+    if VALIDATED.load(Ordering::SeqCst) {
+        validate_agents();
+    }
+}
+
+// Returns how many of the registered agents aren't created by any loaded module
+#[no_mangle]
+pub extern "Rust" fn validate_registrations() -> usize {
+    validate_agents()
+}
@@ -0,0 +1,223 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+// Bookkeeping for the recreated agent vtables in scripts.rs, kept free of any game types so it can be tested.
+// Vtables are shared between every agent created with the same key, and each agent is tracked by the vtable
+// it was given so that only its own destructor ever gives it back. The allocations themselves are up to the
+// caller, this only says when one has to be made or freed.
+pub struct VtablePool<K: Hash + Eq + Clone> {
+    // the vtable new agents with the key are given
+    shared: HashMap<K, usize>,
+    // every vtable that is still in use, with the key it is shared under (None once orphaned) and its agent count
+    vtables: HashMap<usize, (Option<K>, usize)>,
+    // the vtable each live agent holds
+    agents: HashMap<usize, usize>
+}
+
+impl<K: Hash + Eq + Clone> VtablePool<K> {
+    pub fn new() -> Self {
+        Self {
+            shared: HashMap::new(),
+            vtables: HashMap::new(),
+            agents: HashMap::new()
+        }
+    }
+
+    // The vtable for a newly created agent, `create` is only called if there isn't one to share. Also returns a
+    // vtable to free if a destroyed agent we never saw the destructor of was holding the last reference to it
+    pub fn acquire<F: FnOnce() -> usize>(&mut self, key: K, agent: usize, create: F) -> (usize, Option<usize>) {
+        let vtables = &mut self.vtables;
+        let vtable = *self.shared.entry(key.clone()).or_insert_with(|| {
+            let vtable = create();
+            vtables.insert(vtable, (Some(key), 0));
+            vtable
+        });
+        self.vtables.get_mut(&vtable).unwrap().1 += 1;
+        // a new agent at the address of one we never saw destroyed, don't let it keep the old one alive
+        match self.agents.insert(agent, vtable) {
+            Some(previous) if self.release(previous) => (vtable, Some(previous)),
+            _ => (vtable, None)
+        }
+    }
+
+    // Claims the agent's vtable for its destructor. Only the first destructor to ask gets it, so an agent
+    // whose deleting destructor goes through its complete destructor is only counted once
+    pub fn take_agent(&mut self, agent: usize) -> Option<usize> {
+        self.agents.remove(&agent)
+    }
+
+    // Gives back a vtable claimed with take_agent, returns true if it has to be freed now
+    pub fn release(&mut self, vtable: usize) -> bool {
+        let refs = match self.vtables.get_mut(&vtable) {
+            Some((_, refs)) => {
+                *refs -= 1;
+                *refs
+            },
+            None => return false
+        };
+        if refs != 0 {
+            return false;
+        }
+        if let Some((Some(key), _)) = self.vtables.remove(&vtable) {
+            self.shared.remove(&key);
+        }
+        true
+    }
+
+    // Stops sharing the vtables of every matching key, for when the module that created them is unloaded.
+    // Agents that are still alive keep theirs until they are destroyed
+    pub fn orphan<F: Fn(&K) -> bool>(&mut self, should_orphan: F) {
+        let keys: Vec<K> = self.shared.keys().filter(|key| should_orphan(key)).cloned().collect();
+        for key in keys.iter() {
+            if let Some(vtable) = self.shared.remove(key) {
+                if let Some((shared_key, _)) = self.vtables.get_mut(&vtable) {
+                    *shared_key = None;
+                }
+            }
+        }
+    }
+
+    pub fn live_vtables(&self) -> usize {
+        self.vtables.len()
+    }
+
+    pub fn live_agents(&self) -> usize {
+        self.agents.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // stands in for the allocator, hands out fake addresses and checks every free matches an allocation
+    struct Allocations {
+        next: usize,
+        live: Vec<usize>
+    }
+
+    impl Allocations {
+        fn new() -> Self {
+            Self { next: 0x1000, live: Vec::new() }
+        }
+
+        fn alloc(&mut self) -> usize {
+            self.next += 0x98;
+            self.live.push(self.next);
+            self.next
+        }
+
+        fn free(&mut self, vtable: usize) {
+            let index = self.live.iter().position(|live| *live == vtable).expect("freed a vtable that isn't allocated");
+            self.live.remove(index);
+        }
+    }
+
+    fn destroy(pool: &mut VtablePool<&'static str>, allocations: &mut Allocations, agent: usize) {
+        if let Some(vtable) = pool.take_agent(agent) {
+            if pool.release(vtable) {
+                allocations.free(vtable);
+            }
+        }
+    }
+
+    #[test]
+    fn agents_with_the_same_key_share() {
+        let mut pool = VtablePool::new();
+        let mut allocations = Allocations::new();
+        let (first, _) = pool.acquire("mario", 1, || allocations.alloc());
+        let (second, _) = pool.acquire("mario", 2, || allocations.alloc());
+        let (other, _) = pool.acquire("luigi", 3, || allocations.alloc());
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+        assert_eq!(allocations.live.len(), 2);
+    }
+
+    #[test]
+    fn create_destroy_loop() {
+        let mut pool = VtablePool::new();
+        let mut allocations = Allocations::new();
+        for round in 0..100 {
+            let agents: Vec<usize> = (0..8).map(|index| round * 0x100 + index).collect();
+            for agent in agents.iter() {
+                let key = if agent % 2 == 0 { "mario" } else { "luigi" };
+                pool.acquire(key, *agent, || allocations.alloc());
+            }
+            assert_eq!(pool.live_vtables(), 2);
+            assert_eq!(pool.live_agents(), 8);
+            for agent in agents.iter() {
+                destroy(&mut pool, &mut allocations, *agent);
+            }
+            assert_eq!(pool.live_vtables(), 0);
+            assert_eq!(pool.live_agents(), 0);
+            assert!(allocations.live.is_empty());
+        }
+    }
+
+    #[test]
+    fn vtable_lives_until_last_agent() {
+        let mut pool = VtablePool::new();
+        let mut allocations = Allocations::new();
+        let (vtable, _) = pool.acquire("mario", 1, || allocations.alloc());
+        pool.acquire("mario", 2, || allocations.alloc());
+        destroy(&mut pool, &mut allocations, 1);
+        assert_eq!(allocations.live, vec![vtable]);
+        destroy(&mut pool, &mut allocations, 2);
+        assert!(allocations.live.is_empty());
+    }
+
+    #[test]
+    fn nested_destructors_release_once() {
+        let mut pool = VtablePool::new();
+        let mut allocations = Allocations::new();
+        pool.acquire("mario", 1, || allocations.alloc());
+        pool.acquire("mario", 2, || allocations.alloc());
+        // the deleting dtor claims the agent, then the complete dtor it calls finds nothing left
+        let outer = pool.take_agent(1);
+        let inner = pool.take_agent(1);
+        assert!(outer.is_some());
+        assert!(inner.is_none());
+        assert!(!pool.release(outer.unwrap()));
+        assert_eq!(allocations.live.len(), 1);
+    }
+
+    #[test]
+    fn orphaned_vtables_outlive_their_module() {
+        let mut pool = VtablePool::new();
+        let mut allocations = Allocations::new();
+        let (old, _) = pool.acquire("mario", 1, || allocations.alloc());
+        pool.orphan(|key| *key == "mario");
+        // still held by agent 1, and no longer handed out
+        assert_eq!(allocations.live, vec![old]);
+        let (new, _) = pool.acquire("mario", 2, || allocations.alloc());
+        assert_ne!(old, new);
+        destroy(&mut pool, &mut allocations, 1);
+        assert_eq!(allocations.live, vec![new]);
+        destroy(&mut pool, &mut allocations, 2);
+        assert!(allocations.live.is_empty());
+        assert_eq!(pool.live_vtables(), 0);
+    }
+
+    #[test]
+    fn reused_agent_address_drops_the_stale_vtable() {
+        let mut pool = VtablePool::new();
+        let mut allocations = Allocations::new();
+        let (old, _) = pool.acquire("mario", 1, || allocations.alloc());
+        // agent 1 was never seen being destroyed, and something new was created at its address
+        let (new, stale) = pool.acquire("luigi", 1, || allocations.alloc());
+        assert_eq!(stale, Some(old));
+        allocations.free(old);
+        assert_eq!(allocations.live, vec![new]);
+        assert_eq!(pool.live_vtables(), 1);
+        // and the reference the stale agent held is gone with it
+        let (again, _) = pool.acquire("mario", 2, || allocations.alloc());
+        assert_ne!(again, old);
+    }
+
+    #[test]
+    fn unknown_agents_and_vtables_are_ignored() {
+        let mut pool: VtablePool<&'static str> = VtablePool::new();
+        assert!(pool.take_agent(1).is_none());
+        assert!(!pool.release(0x1234));
+    }
+}